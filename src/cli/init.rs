@@ -41,6 +41,18 @@ colors = true
 parallel = 1
 retries = 0
 retry_delay = 1000
+# Compare [assert] values as strings, so "200" also matches 200
+# loose_assertions = true
+# Fail when a {{variable}} has no value instead of sending an empty string
+# strict_vars = true
+# OS environment variables {{name}} may fall back to (glob patterns)
+# env_allowlist = ["HOME", "CI_*"]
+
+[state]
+# [post-response] variables saved to .reqx/state/<env>.toml for later runs
+# persist = ["access_token"]
+# How long each saved variable stays valid
+# ttl = { access_token = "55m" }
 "#;
 
     fs::write(".reqx/config.toml", config_content)
@@ -91,6 +103,7 @@ status = 200
     // Create .gitignore additions
     let gitignore = r#"# reqx
 .reqx/environments/*.local.toml
+.reqx/state/
 *.reqx.log
 "#;
 
@@ -11,9 +11,11 @@ pub mod health;
 pub mod import;
 pub mod init;
 pub mod run;
+pub mod state;
 pub mod validate;
 pub mod watch;
 
+use chrono::{DateTime, Utc};
 use clap::{Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
 
@@ -101,6 +103,30 @@ pub enum Commands {
         /// Validate without executing
         #[arg(long)]
         dry_run: bool,
+
+        /// Rewrite stored snapshots with the current responses
+        #[arg(long)]
+        update_snapshots: bool,
+
+        /// Fail when a {{variable}} has no value
+        #[arg(long)]
+        strict_vars: bool,
+
+        /// Seed for random dynamic variables, to replay a previous run
+        #[arg(long)]
+        seed: Option<u64>,
+
+        /// Freeze the clock for dynamic variables (RFC 3339 timestamp)
+        #[arg(long, value_parser = parse_timestamp)]
+        now: Option<DateTime<Utc>>,
+
+        /// Run each request once per row of a CSV or JSON file
+        #[arg(long)]
+        data: Option<PathBuf>,
+
+        /// Data column that names each iteration (default: the row number)
+        #[arg(long, requires = "data")]
+        data_name: Option<String>,
     },
 
     /// Validate .reqx files syntax
@@ -157,6 +183,12 @@ pub enum Commands {
         action: ConfigAction,
     },
 
+    /// Inspect or clear variables saved between runs
+    State {
+        #[command(subcommand)]
+        action: StateAction,
+    },
+
     /// Import from other formats
     Import {
         /// Source format
@@ -197,6 +229,25 @@ pub enum ConfigAction {
     Edit,
 }
 
+#[derive(Subcommand, Debug)]
+pub enum StateAction {
+    /// Show saved variables and when they expire
+    Show {
+        /// Environment whose state to show
+        #[arg(short, long)]
+        env: Option<String>,
+    },
+    /// Remove saved variables, or all of them when none are named
+    Clear {
+        /// Variables to remove
+        names: Vec<String>,
+
+        /// Environment whose state to clear
+        #[arg(short, long)]
+        env: Option<String>,
+    },
+}
+
 #[derive(ValueEnum, Clone, Debug)]
 pub enum OutputFormat {
     Table,
@@ -236,3 +287,10 @@ fn parse_key_value(s: &str) -> Result<(String, String), String> {
         .ok_or_else(|| format!("invalid KEY=VALUE: no `=` found in `{s}`"))?;
     Ok((s[..pos].to_string(), s[pos + 1..].to_string()))
 }
+
+/// Parse an RFC 3339 timestamp such as 2026-01-01T00:00:00Z
+fn parse_timestamp(s: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(s)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| format!("invalid timestamp `{s}`: {e}"))
+}
@@ -5,13 +5,18 @@
 //! Execute API requests
 
 use crate::config::Config;
-use crate::http::Client;
-use crate::output::{OutputFormatter, TableFormatter, JsonFormatter, JunitFormatter, TapFormatter};
+use crate::http::{Client, Response};
+use crate::output::{OutputFormatter, RunInfo, TableFormatter, JsonFormatter, JunitFormatter, TapFormatter};
 use crate::parser::{parse_file, ReqxFile};
-use crate::runtime::{ExecutionContext, ExecutionResult};
+use crate::runtime::{
+    dependency_key, load_data, order_by_dependencies, state_path, AssertionResult, Attempt,
+    ExecutionContext, ExecutionResult,
+};
 use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
 use colored::Colorize;
 use glob::glob;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Instant;
@@ -33,13 +38,22 @@ pub struct RunOptions {
     pub filter: Option<String>,
     pub exclude: Option<String>,
     pub dry_run: bool,
+    pub update_snapshots: bool,
+    pub strict_vars: bool,
+    pub seed: Option<u64>,
+    pub now: Option<DateTime<Utc>>,
+    pub data: Option<PathBuf>,
+    pub data_name: Option<String>,
     pub verbose: bool,
     pub no_color: bool,
 }
 
 pub async fn execute(options: RunOptions) -> Result<()> {
     // Load configuration
-    let config = Config::load(options.env.as_deref())?;
+    let mut config = Config::load(options.env.as_deref())?;
+    if options.strict_vars {
+        config.execution.strict_vars = true;
+    }
     
     // Discover files to run
     let files = discover_files(&options.path, options.filter.as_deref(), options.exclude.as_deref())?;
@@ -69,6 +83,13 @@ pub async fn execute(options: RunOptions) -> Result<()> {
         }
     }
 
+    // Run dependencies first
+    let selected: HashSet<PathBuf> = parsed_files
+        .iter()
+        .map(|(path, _)| dependency_key(path))
+        .collect();
+    let parsed_files = order_by_dependencies(parsed_files)?;
+
     if options.dry_run {
         println!("{}", "Dry run - validation complete".cyan());
         for (path, _) in &parsed_files {
@@ -87,6 +108,9 @@ pub async fn execute(options: RunOptions) -> Result<()> {
 
     // Create execution context
     let mut context = ExecutionContext::new(config);
+    context.update_snapshots = options.update_snapshots;
+    context.set_generators(options.seed, options.now);
+    context.load_state(state_path(options.env.as_deref()))?;
     
     // Add CLI variables
     for (key, value) in &options.var {
@@ -95,32 +119,7 @@ pub async fn execute(options: RunOptions) -> Result<()> {
 
     // Execute requests
     let start_time = Instant::now();
-    let mut results: Vec<ExecutionResult> = Vec::new();
-
-    if options.parallel <= 1 {
-        // Sequential execution
-        for (path, reqx_file) in parsed_files {
-            let result = execute_request(&client, &mut context, &path, &reqx_file).await;
-            
-            if options.verbose {
-                print_result_verbose(&result);
-            }
-            
-            let failed = result.failed;
-            results.push(result);
-            
-            if failed && options.fail_fast {
-                break;
-            }
-        }
-    } else {
-        // TODO: Parallel execution
-        // For now, fall back to sequential
-        for (path, reqx_file) in parsed_files {
-            let result = execute_request(&client, &mut context, &path, &reqx_file).await;
-            results.push(result);
-        }
-    }
+    let results = execute_files(&client, &mut context, &options, parsed_files, &selected).await;
 
     let total_duration = start_time.elapsed();
 
@@ -137,7 +136,12 @@ pub async fn execute(options: RunOptions) -> Result<()> {
         }
     };
 
-    let output = formatter.format(&results, total_duration);
+    let run = RunInfo {
+        duration: total_duration,
+        seed: context.seed(),
+        now: options.now,
+    };
+    let output = formatter.format(&results, &run);
 
     if let Some(output_file) = options.output_file {
         std::fs::write(&output_file, &output)
@@ -203,6 +207,135 @@ fn discover_files(
     Ok(files)
 }
 
+/// Run files in order, stopping at the first failure with `--fail-fast`.
+/// `selected` holds the dependency keys of the files the user selected, as
+/// opposed to dependencies they pulled in.
+async fn execute_files(
+    client: &Client,
+    context: &mut ExecutionContext,
+    options: &RunOptions,
+    files: Vec<(PathBuf, ReqxFile)>,
+    selected: &HashSet<PathBuf>,
+) -> Vec<ExecutionResult> {
+    let mut results: Vec<ExecutionResult> = Vec::new();
+    let mut blocked: HashMap<PathBuf, String> = HashMap::new();
+
+    if options.parallel <= 1 {
+        // Sequential execution
+        for (path, reqx_file) in files {
+            let file_results = execute_file(client, context, options, &mut blocked, selected, &path, &reqx_file).await;
+            
+            if options.verbose {
+                file_results.iter().for_each(print_result_verbose);
+            }
+            
+            let failed = file_results.iter().any(|r| r.failed);
+            results.extend(file_results);
+            
+            if failed && options.fail_fast {
+                break;
+            }
+        }
+    } else {
+        // TODO: Parallel execution
+        // For now, fall back to sequential
+        for (path, reqx_file) in files {
+            results.extend(execute_file(client, context, options, &mut blocked, selected, &path, &reqx_file).await);
+        }
+    }
+
+    results
+}
+
+/// Run a request once, or once per row of its data file. `--data` takes
+/// precedence over the file's `[data]` section but applies only to selected
+/// files, so a dependency such as a login runs once.
+///
+/// `blocked` maps files that failed, or were skipped because a dependency
+/// failed, to the file that failed. Their dependants are skipped.
+async fn execute_file(
+    client: &Client,
+    context: &mut ExecutionContext,
+    options: &RunOptions,
+    blocked: &mut HashMap<PathBuf, String>,
+    selected: &HashSet<PathBuf>,
+    path: &PathBuf,
+    reqx_file: &ReqxFile,
+) -> Vec<ExecutionResult> {
+    let key = dependency_key(path);
+    let cause = reqx_file
+        .meta
+        .depends_on
+        .iter()
+        .find_map(|dependency| blocked.get(&dependency_key(dependency)))
+        .cloned();
+    if let Some(cause) = cause {
+        let skipped = format!("dependency {} failed", cause);
+        blocked.insert(key, cause);
+        let result = ExecutionResult::not_run(
+            path,
+            &reqx_file.request.method,
+            &reqx_file.request.url,
+            std::time::Duration::ZERO,
+        );
+        return vec![result.skipped(skipped)];
+    }
+
+    let is_selected = selected.contains(&key);
+    let results = execute_iterations(client, context, options, is_selected, path, reqx_file).await;
+    if results.iter().any(|r| r.failed) {
+        blocked.insert(key, path.display().to_string());
+    }
+    results
+}
+
+async fn execute_iterations(
+    client: &Client,
+    context: &mut ExecutionContext,
+    options: &RunOptions,
+    selected: bool,
+    path: &PathBuf,
+    reqx_file: &ReqxFile,
+) -> Vec<ExecutionResult> {
+    let data = match (&options.data, &reqx_file.data) {
+        (Some(file), _) if selected => Some((file.clone(), options.data_name.clone())),
+        (_, Some(section)) => Some((section.file.clone(), section.name.clone())),
+        (_, None) => None,
+    };
+    let Some((data_file, data_name)) = data else {
+        return vec![execute_request(client, context, path, reqx_file).await];
+    };
+
+    let rows = match load_data(&data_file, data_name.as_deref()) {
+        Ok(rows) => rows,
+        Err(e) => {
+            let result = ExecutionResult::not_run(
+                path,
+                &reqx_file.request.method,
+                &reqx_file.request.url,
+                std::time::Duration::ZERO,
+            );
+            return vec![result.error(format!("Data error: {:#}", e))];
+        }
+    };
+
+    let mut results = Vec::new();
+    for row in rows {
+        let saved = context.set_row(&row);
+        let mut result = execute_request(client, context, path, reqx_file).await;
+        context.restore_variables(saved);
+
+        result.iteration = Some(row.label);
+        let failed = result.failed;
+        results.push(result);
+
+        if failed && options.fail_fast {
+            break;
+        }
+    }
+    results
+}
+
 async fn execute_request(
     client: &Client,
     context: &mut ExecutionContext,
@@ -210,43 +343,62 @@ async fn execute_request(
     reqx_file: &ReqxFile,
 ) -> ExecutionResult {
     let start = Instant::now();
+    let not_run = |url: &str| {
+        ExecutionResult::not_run(path, &reqx_file.request.method, url, start.elapsed())
+    };
     
+    // Check [meta] skip_if / run_if
+    let skipped = match context.skip_reason(reqx_file) {
+        Ok(skipped) => skipped,
+        Err(e) => {
+            return not_run(&reqx_file.request.url).error(format!("Condition error: {:#}", e));
+        }
+    };
+    if let Some(reason) = skipped {
+        return not_run(&reqx_file.request.url).skipped(reason);
+    }
+
+    // Compute [pre-request] variables
+    if let Err(e) = context.run_pre_request(reqx_file) {
+        return not_run(&reqx_file.request.url).error(format!("Pre-request error: {:#}", e));
+    }
+
     // Interpolate variables
-    let interpolated = match context.interpolate(reqx_file) {
+    let mut interpolated = match context.interpolate(reqx_file) {
         Ok(r) => r,
         Err(e) => {
-            return ExecutionResult {
-                file: path.clone(),
-                method: reqx_file.request.method.clone(),
-                url: reqx_file.request.url.clone(),
-                status: None,
-                duration: start.elapsed(),
-                assertions: vec![],
-                failed: true,
-                error: Some(format!("Interpolation error: {}", e)),
-            };
+            return not_run(&reqx_file.request.url).error(format!("Interpolation error: {:#}", e));
         }
     };
 
-    // Execute HTTP request
-    let response = match client.execute(&interpolated).await {
+    // Run pre-request script on the final request
+    if let Err(e) = context.run_pre_request_script(&mut interpolated) {
+        let result = ExecutionResult::not_run(
+            path,
+            &interpolated.request.method,
+            &interpolated.request.url,
+            start.elapsed(),
+        );
+        return result.error(format!("Pre-request script error: {}", e));
+    }
+
+    // Execute HTTP request, polling until [retry] until holds
+    let mut attempts = Vec::new();
+    let (response, until) = match send(client, context, &interpolated, &mut attempts).await {
         Ok(r) => r,
         Err(e) => {
             return ExecutionResult {
-                file: path.clone(),
-                method: reqx_file.request.method.clone(),
-                url: interpolated.request.url.clone(),
-                status: None,
-                duration: start.elapsed(),
-                assertions: vec![],
-                failed: true,
-                error: Some(format!("HTTP error: {}", e)),
+                attempts,
+                ..not_run(&interpolated.request.url).error(format!("HTTP error: {}", e))
             };
         }
     };
 
-    // Run assertions
-    let assertion_results = context.run_assertions(&interpolated, &response);
+    // Run assertions on the final response, then the post-response script
+    // which may add failures
+    let mut assertion_results: Vec<AssertionResult> = until.into_iter().collect();
+    assertion_results.extend(context.run_assertions(&interpolated, &response));
+    assertion_results.extend(context.run_post_response_script(&interpolated, &response));
     let failed = assertion_results.iter().any(|a| !a.passed);
 
     // Run post-response scripts
@@ -265,21 +417,67 @@ async fn execute_request(
         assertions: assertion_results,
         failed,
         error: None,
+        skipped: None,
+        iteration: None,
+        attempts,
+    }
+}
+
+/// Send a request, re-sending it while its `[retry] until` condition does
+/// not hold and recording each attempt. Also returns the condition's
+/// failure when the attempts ran out.
+async fn send(
+    client: &Client,
+    context: &ExecutionContext,
+    reqx_file: &ReqxFile,
+    attempts: &mut Vec<Attempt>,
+) -> Result<(Response, Option<AssertionResult>)> {
+    let Some(retry) = &reqx_file.retry else {
+        return Ok((client.execute(reqx_file).await?, None));
+    };
+
+    let start = Instant::now();
+    let mut interval = retry.interval;
+    loop {
+        let started = start.elapsed();
+        let response = client.execute(reqx_file).await?;
+        let until = context.check_until(reqx_file, &response, attempts.len() + 1);
+        let matched = until.as_ref().is_some_and(|u| u.passed);
+        attempts.push(Attempt {
+            started,
+            duration: response.duration,
+            status: response.status,
+            matched,
+        });
+
+        if matched || attempts.len() >= retry.max_attempts as usize {
+            return Ok((response, until.filter(|u| !u.passed)));
+        }
+        tokio::time::sleep(interval).await;
+        interval = retry.next_interval(interval);
     }
 }
 
 fn print_result_verbose(result: &ExecutionResult) {
-    let status_str = result
-        .status
-        .map(|s| s.to_string())
-        .unwrap_or_else(|| "ERR".to_string());
+    let status_str = match (&result.skipped, result.status) {
+        (Some(_), _) => "SKIP".to_string(),
+        (None, Some(status)) => status.to_string(),
+        (None, None) => "ERR".to_string(),
+    };
     
     let color = if result.failed { "red" } else { "green" };
     
+    let iteration = result
+        .iteration
+        .as_ref()
+        .map(|i| format!(" [{}]", i))
+        .unwrap_or_default();
+    
     println!(
-        "{} {} {} ({:?})",
+        "{} {}{} {} ({:?})",
         result.method,
         result.url,
+        iteration,
         if result.failed {
             status_str.red()
         } else {
@@ -288,3 +486,87 @@ fn print_result_verbose(result: &ExecutionResult) {
         result.duration
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::HttpConfig;
+    use std::path::Path;
+    use wiremock::matchers::path;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn options(data: &Path) -> RunOptions {
+        RunOptions {
+            path: PathBuf::from("."),
+            env: None,
+            output: OutputFormat::Silent,
+            output_file: None,
+            fail_fast: false,
+            parallel: 1,
+            timeout: 5000,
+            retries: 0,
+            retry_delay: 0,
+            var: vec![],
+            var_file: None,
+            filter: None,
+            exclude: None,
+            dry_run: false,
+            update_snapshots: false,
+            strict_vars: false,
+            seed: None,
+            now: None,
+            data: Some(data.to_path_buf()),
+            data_name: None,
+            verbose: false,
+            no_color: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_data_rows_run_dependencies_once() {
+        let server = MockServer::start().await;
+        for (route, calls) in [("/login", 1), ("/orders", 2)] {
+            Mock::given(path(route))
+                .respond_with(ResponseTemplate::new(200))
+                .expect(calls)
+                .mount(&server)
+                .await;
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let login = dir.path().join("login.reqx");
+        let orders = dir.path().join("orders.reqx");
+        let rows = dir.path().join("rows.csv");
+        std::fs::write(
+            &login,
+            format!("[request]\nmethod = \"POST\"\nurl = \"{}/login\"\n", server.uri()),
+        )
+        .unwrap();
+        std::fs::write(
+            &orders,
+            format!(
+                "[meta]\ndepends_on = [\"login.reqx\"]\n\n[request]\nmethod = \"GET\"\nurl = \"{}/orders?id={{{{id}}}}\"\n",
+                server.uri()
+            ),
+        )
+        .unwrap();
+        std::fs::write(&rows, "id\n1\n2\n").unwrap();
+
+        // Only orders.reqx is selected; login.reqx is pulled in
+        let files = vec![(orders.clone(), parse_file(&orders).unwrap())];
+        let selected = HashSet::from([dependency_key(&orders)]);
+        let files = order_by_dependencies(files).unwrap();
+
+        let client = Client::new(5000, 0, 0, HttpConfig::default()).unwrap();
+        let mut context = ExecutionContext::new(Config::default());
+        let options = options(&rows);
+        let results = execute_files(&client, &mut context, &options, files, &selected).await;
+
+        let names: Vec<String> = results.iter().map(|r| r.name()).collect();
+        assert_eq!(
+            names,
+            vec!["login.reqx", "orders.reqx [row 1]", "orders.reqx [row 2]"]
+        );
+        assert!(results.iter().all(|r| !r.failed), "{:?}", results);
+    }
+}
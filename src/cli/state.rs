@@ -0,0 +1,84 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Inspect and clear variables persisted between runs
+
+use super::StateAction;
+use crate::runtime::{state_path, State};
+use anyhow::{Context, Result};
+use chrono::Utc;
+use colored::Colorize;
+use std::fs;
+
+/// Longest value shown before it is cut short
+const MAX_VALUE_WIDTH: usize = 60;
+
+pub async fn execute(action: StateAction) -> Result<()> {
+    match action {
+        StateAction::Show { env } => {
+            let path = state_path(env.as_deref());
+            let state = State::load(&path)?;
+
+            if state.variables.is_empty() {
+                println!(
+                    "{}",
+                    format!("No saved variables in {}", path.display()).yellow()
+                );
+                return Ok(());
+            }
+
+            let now = Utc::now();
+            println!("{}", path.display().to_string().cyan());
+            for (name, stored) in &state.variables {
+                let mut value = stored.value().to_string();
+                if value.chars().count() > MAX_VALUE_WIDTH {
+                    value = value.chars().take(MAX_VALUE_WIDTH).collect::<String>() + "…";
+                }
+
+                let expiry = match stored.expires_at {
+                    Some(_) if stored.is_expired(now) => "expired".red().to_string(),
+                    Some(expires_at) => format!("expires {}", expires_at.to_rfc3339()),
+                    None => "never expires".to_string(),
+                };
+
+                println!(
+                    "  {} = {}  ({}, saved {})",
+                    name.green(),
+                    value,
+                    expiry,
+                    stored.saved_at.to_rfc3339()
+                );
+            }
+        }
+        StateAction::Clear { names, env } => {
+            let path = state_path(env.as_deref());
+            if !path.exists() {
+                println!(
+                    "{}",
+                    format!("No saved variables in {}", path.display()).yellow()
+                );
+                return Ok(());
+            }
+
+            if names.is_empty() {
+                fs::remove_file(&path)
+                    .with_context(|| format!("Failed to remove {}", path.display()))?;
+                println!("{}", format!("✓ Cleared {}", path.display()).green());
+                return Ok(());
+            }
+
+            let mut state = State::load(&path)?;
+            for name in &names {
+                if state.variables.remove(name).is_some() {
+                    println!("{}", format!("✓ Removed {}", name).green());
+                } else {
+                    println!("{}", format!("{} is not saved", name).yellow());
+                }
+            }
+            state.save(&path)?;
+        }
+    }
+
+    Ok(())
+}
@@ -67,6 +67,12 @@ pub async fn execute(
                         filter: filter.clone(),
                         exclude: None,
                         dry_run: false,
+                        update_snapshots: false,
+                        strict_vars: false,
+                        seed: None,
+                        now: None,
+                        data: None,
+                        data_name: None,
                         verbose: false,
                         no_color: false,
                     };
@@ -25,6 +25,27 @@ pub struct Config {
 
     #[serde(default)]
     pub variables: HashMap<String, String>,
+
+    #[serde(default)]
+    pub state: StateConfig,
+
+    /// Environment the configuration was loaded for
+    #[serde(skip)]
+    pub environment: Option<String>,
+}
+
+/// Captured variables kept between runs in `.reqx/state/<env>.toml`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StateConfig {
+    /// Names of `[post-response]` variables to save. Nothing is saved when
+    /// empty.
+    #[serde(default)]
+    pub persist: Vec<String>,
+
+    /// How long a saved variable stays valid, such as `"55m"` or `"12h"`.
+    /// Variables without one never expire.
+    #[serde(default)]
+    pub ttl: HashMap<String, String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -55,6 +76,20 @@ pub struct ExecutionConfig {
 
     #[serde(default = "default_retry_delay")]
     pub retry_delay: u64,
+
+    /// Compare [assert] values by their string form instead of by JSON type
+    #[serde(default)]
+    pub loose_assertions: bool,
+
+    /// Fail interpolation when a `{{variable}}` has no value instead of
+    /// substituting an empty string
+    #[serde(default)]
+    pub strict_vars: bool,
+
+    /// OS environment variables `{{name}}` may fall back to, as glob
+    /// patterns. All are allowed when unset.
+    #[serde(default)]
+    pub env_allowlist: Option<Vec<String>>,
 }
 
 impl Default for ExecutionConfig {
@@ -63,6 +98,9 @@ impl Default for ExecutionConfig {
             parallel: 1,
             retries: 0,
             retry_delay: 1000,
+            loose_assertions: false,
+            strict_vars: false,
+            env_allowlist: None,
         }
     }
 }
@@ -117,6 +155,7 @@ impl Config {
             }
         }
 
+        config.environment = env.map(String::from);
         Ok(config)
     }
 }
@@ -163,5 +202,8 @@ mod tests {
         let config = Config::default();
         assert_eq!(config.execution.parallel, 1);
         assert!(config.output.colors);
+        assert!(!config.execution.loose_assertions);
+        assert!(!config.execution.strict_vars);
+        assert!(config.state.persist.is_empty());
     }
 }
@@ -4,11 +4,10 @@
 
 //! HTTP client implementation
 
-use super::{HttpConfig, Response};
+use super::{Headers, HttpConfig, Response};
 use crate::parser::{BodySection, ReqxFile};
 use anyhow::{Context, Result};
 use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
-use std::collections::HashMap;
 use std::str::FromStr;
 use std::time::{Duration, Instant};
 
@@ -129,7 +128,7 @@ impl Client {
         let status = response.status().as_u16();
 
         // Parse response headers
-        let response_headers: HashMap<String, String> = response
+        let response_headers: Headers = response
             .headers()
             .iter()
             .map(|(k, v)| {
@@ -145,9 +144,10 @@ impl Client {
             .text()
             .await
             .map_err(|e| RequestError::Network(e.to_string()))?;
+        let size = body_text.len();
 
         let body: serde_json::Value = serde_json::from_str(&body_text).unwrap_or_else(|_| {
-            serde_json::Value::String(body_text)
+            serde_json::Value::String(body_text.clone())
         });
 
         let duration = start.elapsed();
@@ -156,6 +156,8 @@ impl Client {
             status,
             headers: response_headers,
             body,
+            raw: body_text,
+            size,
             duration,
         })
     }
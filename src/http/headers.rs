@@ -0,0 +1,73 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Response header multimap
+
+use serde::{Deserialize, Serialize};
+
+/// Response headers in the order received. Names match case-insensitively and
+/// repeated headers such as `Set-Cookie` keep every value.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Headers(Vec<(String, String)>);
+
+impl Headers {
+    /// First value for a header
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.get_all(name).into_iter().next()
+    }
+
+    /// Every value for a header, in the order received
+    pub fn get_all(&self, name: &str) -> Vec<&str> {
+        self.0
+            .iter()
+            .filter(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    /// Distinct header names, in the order first received
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for (name, _) in &self.0 {
+            if !names.iter().any(|n| n.eq_ignore_ascii_case(name)) {
+                names.push(name);
+            }
+        }
+        names
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for Headers {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self(
+            iter.into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_headers_case_insensitive_multimap() {
+        let headers: Headers = [
+            ("Content-Type", "application/json"),
+            ("Set-Cookie", "sid=abc; HttpOnly"),
+            ("set-cookie", "theme=dark"),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(headers.get("content-type"), Some("application/json"));
+        assert_eq!(
+            headers.get_all("SET-COOKIE"),
+            vec!["sid=abc; HttpOnly", "theme=dark"]
+        );
+        assert_eq!(headers.names(), vec!["Content-Type", "Set-Cookie"]);
+        assert!(headers.get("link").is_none());
+    }
+}
@@ -5,22 +5,51 @@
 //! HTTP client module
 
 mod client;
+mod headers;
 
 pub use client::Client;
+pub use headers::Headers;
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 use std::time::Duration;
 
 /// HTTP response
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Response {
     pub status: u16,
-    pub headers: HashMap<String, String>,
+    pub headers: Headers,
     pub body: serde_json::Value,
+    /// The body exactly as received
+    #[serde(default)]
+    pub raw: String,
+    /// Length of the response body in bytes
+    pub size: usize,
     pub duration: Duration,
 }
 
+impl Response {
+    /// The body as text, with the whitespace and key order the server sent
+    pub fn text(&self) -> &str {
+        &self.raw
+    }
+}
+
+#[cfg(test)]
+impl Response {
+    /// A response with a JSON body and no headers
+    pub fn test_json(status: u16, body: serde_json::Value) -> Self {
+        let raw = body.to_string();
+        Self {
+            status,
+            headers: Headers::default(),
+            size: raw.len(),
+            body,
+            raw,
+            duration: Duration::from_millis(5),
+        }
+    }
+}
+
 /// HTTP configuration
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct HttpConfig {
@@ -45,6 +45,12 @@ async fn main() -> Result<()> {
             filter,
             exclude,
             dry_run,
+            update_snapshots,
+            strict_vars,
+            seed,
+            now,
+            data,
+            data_name,
         } => {
             cli::run::execute(cli::run::RunOptions {
                 path,
@@ -61,6 +67,12 @@ async fn main() -> Result<()> {
                 filter,
                 exclude,
                 dry_run,
+                update_snapshots,
+                strict_vars,
+                seed,
+                now,
+                data,
+                data_name,
                 verbose: cli.verbose,
                 no_color: cli.no_color,
             })
@@ -88,6 +100,9 @@ async fn main() -> Result<()> {
         Commands::Config { action } => {
             cli::config::execute(action).await?;
         }
+        Commands::State { action } => {
+            cli::state::execute(action).await?;
+        }
         Commands::Import { format, path } => {
             cli::import::execute(format, path).await?;
         }
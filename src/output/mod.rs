@@ -4,12 +4,23 @@
 
 //! Output formatters for test results
 
-use crate::runtime::ExecutionResult;
+use crate::runtime::{DiffEntry, DiffKind, ExecutionResult, Outcome};
+use chrono::{DateTime, Utc};
 use colored::Colorize;
 use std::time::Duration;
 
+/// Run-wide details reported alongside the results
+#[derive(Debug, Clone)]
+pub struct RunInfo {
+    pub duration: Duration,
+    /// Seed of the random generator, for replaying with `--seed`
+    pub seed: u64,
+    /// Clock frozen with `--now`, if any
+    pub now: Option<DateTime<Utc>>,
+}
+
 pub trait OutputFormatter {
-    fn format(&self, results: &[ExecutionResult], total_duration: Duration) -> String;
+    fn format(&self, results: &[ExecutionResult], run: &RunInfo) -> String;
 }
 
 /// Table formatter (default, colorized)
@@ -23,16 +34,48 @@ impl TableFormatter {
     }
 }
 
+impl TableFormatter {
+    fn format_diff(&self, entry: &DiffEntry) -> String {
+        let line = format_diff_entry(entry);
+        if !self.colorized {
+            return line;
+        }
+        match entry.kind {
+            DiffKind::Added => line.green().to_string(),
+            DiffKind::Removed => line.red().to_string(),
+            DiffKind::Changed => line.yellow().to_string(),
+        }
+    }
+}
+
 impl OutputFormatter for TableFormatter {
-    fn format(&self, results: &[ExecutionResult], total_duration: Duration) -> String {
+    fn format(&self, results: &[ExecutionResult], run: &RunInfo) -> String {
         let mut output = String::new();
 
-        let passed = results.iter().filter(|r| !r.failed).count();
-        let failed = results.iter().filter(|r| r.failed).count();
+        let passed = count(results, Outcome::Passed);
+        let failed = count(results, Outcome::Failed);
+        let skipped = count(results, Outcome::Skipped);
 
         output.push_str(&format!("\n{}\n\n", "─".repeat(60)));
 
         for result in results {
+            let iteration = result
+                .iteration
+                .as_ref()
+                .map(|i| format!(" [{}]", i))
+                .unwrap_or_default();
+
+            if let Some(ref reason) = result.skipped {
+                let line = format!("○ {} {}{} SKIP", result.method, result.url, iteration);
+                if self.colorized {
+                    output.push_str(&line.yellow().to_string());
+                } else {
+                    output.push_str(&line);
+                }
+                output.push_str(&format!("\n  └─ Skipped: {}\n", reason));
+                continue;
+            }
+
             let status_str = result
                 .status
                 .map(|s| s.to_string())
@@ -59,13 +102,21 @@ impl OutputFormatter for TableFormatter {
                 icon.to_string()
             };
 
+            let attempts = match result.attempts.len() {
+                0 => String::new(),
+                1 => ", 1 attempt".to_string(),
+                n => format!(", {} attempts", n),
+            };
+
             output.push_str(&format!(
-                "{} {} {} {} ({:?})\n",
+                "{} {} {}{} {} ({:?}{})\n",
                 icon_display,
                 result.method,
                 result.url,
+                iteration,
                 status_display,
-                result.duration
+                result.duration,
+                attempts
             ));
 
             // Show assertion details for failures
@@ -73,6 +124,9 @@ impl OutputFormatter for TableFormatter {
                 for assertion in &result.assertions {
                     if !assertion.passed {
                         output.push_str(&format!("  └─ {}\n", assertion.message));
+                        for entry in &assertion.diff {
+                            output.push_str(&format!("       {}\n", self.format_diff(entry)));
+                        }
                     }
                 }
                 if let Some(ref error) = result.error {
@@ -84,11 +138,13 @@ impl OutputFormatter for TableFormatter {
         output.push_str(&format!("\n{}\n", "─".repeat(60)));
 
         let summary = format!(
-            "Total: {} | Passed: {} | Failed: {} | Duration: {:?}",
+            "Total: {} | Passed: {} | Failed: {} | Skipped: {} | Duration: {:?} | Seed: {}",
             results.len(),
             passed,
             failed,
-            total_duration
+            skipped,
+            run.duration,
+            run.seed
         );
 
         if self.colorized {
@@ -116,25 +172,40 @@ impl JsonFormatter {
 }
 
 impl OutputFormatter for JsonFormatter {
-    fn format(&self, results: &[ExecutionResult], total_duration: Duration) -> String {
-        let passed = results.iter().filter(|r| !r.failed).count();
-        let failed = results.iter().filter(|r| r.failed).count();
+    fn format(&self, results: &[ExecutionResult], run: &RunInfo) -> String {
+        let passed = count(results, Outcome::Passed);
+        let failed = count(results, Outcome::Failed);
+        let skipped = count(results, Outcome::Skipped);
 
         let output = serde_json::json!({
             "summary": {
                 "total": results.len(),
                 "passed": passed,
                 "failed": failed,
-                "duration_ms": total_duration.as_millis()
+                "skipped": skipped,
+                "duration_ms": run.duration.as_millis(),
+                "seed": run.seed,
+                "now": run.now.map(|now| now.to_rfc3339())
             },
             "results": results.iter().map(|r| {
                 serde_json::json!({
                     "file": r.file.to_string_lossy(),
+                    "iteration": r.iteration,
                     "method": r.method,
                     "url": r.url,
                     "status": r.status,
                     "duration_ms": r.duration.as_millis(),
-                    "passed": !r.failed,
+                    "passed": r.outcome() == Outcome::Passed,
+                    "skipped": r.outcome() == Outcome::Skipped,
+                    "skip_reason": r.skipped,
+                    "attempts": r.attempts.iter().map(|a| {
+                        serde_json::json!({
+                            "started_ms": a.started.as_millis(),
+                            "duration_ms": a.duration.as_millis(),
+                            "status": a.status,
+                            "matched": a.matched
+                        })
+                    }).collect::<Vec<_>>(),
                     "assertions": r.assertions,
                     "error": r.error
                 })
@@ -155,17 +226,18 @@ impl JunitFormatter {
 }
 
 impl OutputFormatter for JunitFormatter {
-    fn format(&self, results: &[ExecutionResult], total_duration: Duration) -> String {
+    fn format(&self, results: &[ExecutionResult], run: &RunInfo) -> String {
         let mut xml = String::new();
         xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
 
         let total = results.len();
-        let failures = results.iter().filter(|r| r.failed).count();
-        let time = total_duration.as_secs_f64();
+        let failures = count(results, Outcome::Failed);
+        let skipped = count(results, Outcome::Skipped);
+        let time = run.duration.as_secs_f64();
 
         xml.push_str(&format!(
-            "<testsuites name=\"reqx\" tests=\"{}\" failures=\"{}\" errors=\"0\" time=\"{:.3}\">\n",
-            total, failures, time
+            "<testsuites name=\"reqx\" tests=\"{}\" failures=\"{}\" errors=\"0\" skipped=\"{}\" time=\"{:.3}\">\n",
+            total, failures, skipped, time
         ));
 
         // Group by directory
@@ -185,27 +257,43 @@ impl OutputFormatter for JunitFormatter {
         }
 
         for (suite_name, suite_results) in suites {
-            let suite_failures = suite_results.iter().filter(|r| r.failed).count();
+            let suite_failures = suite_results
+                .iter()
+                .filter(|r| r.outcome() == Outcome::Failed)
+                .count();
+            let suite_skipped = suite_results
+                .iter()
+                .filter(|r| r.outcome() == Outcome::Skipped)
+                .count();
             let suite_time: f64 = suite_results.iter().map(|r| r.duration.as_secs_f64()).sum();
 
             xml.push_str(&format!(
-                "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">\n",
+                "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n",
                 escape_xml(&suite_name),
                 suite_results.len(),
                 suite_failures,
+                suite_skipped,
                 suite_time
             ));
 
+            xml.push_str(&format!(
+                "    <properties>\n      <property name=\"seed\" value=\"{}\"/>\n",
+                run.seed
+            ));
+            if let Some(now) = run.now {
+                xml.push_str(&format!(
+                    "      <property name=\"now\" value=\"{}\"/>\n",
+                    now.to_rfc3339()
+                ));
+            }
+            xml.push_str("    </properties>\n");
+
             for result in suite_results {
-                let test_name = result
-                    .file
-                    .file_name()
-                    .and_then(|n| n.to_str())
-                    .unwrap_or("unknown");
+                let test_name = result.name();
 
                 xml.push_str(&format!(
                     "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\">\n",
-                    escape_xml(test_name),
+                    escape_xml(&test_name),
                     escape_xml(&suite_name),
                     result.duration.as_secs_f64()
                 ));
@@ -223,9 +311,21 @@ impl OutputFormatter for JunitFormatter {
                     ));
                 }
 
+                if !result.attempts.is_empty() {
+                    xml.push_str(&format!(
+                        "        <property name=\"attempts\" value=\"{}\"/>\n",
+                        result.attempts.len()
+                    ));
+                }
+
                 xml.push_str("      </properties>\n");
 
-                if result.failed {
+                if let Some(ref reason) = result.skipped {
+                    xml.push_str(&format!(
+                        "      <skipped message=\"{}\"/>\n",
+                        escape_xml(reason)
+                    ));
+                } else if result.failed {
                     let message = result
                         .assertions
                         .iter()
@@ -234,7 +334,19 @@ impl OutputFormatter for JunitFormatter {
                         .collect::<Vec<_>>()
                         .join("; ");
 
-                    let error_message = result.error.clone().unwrap_or_default();
+                    let mut error_message = result.error.clone().unwrap_or_default();
+                    for assertion in result.assertions.iter().filter(|a| !a.passed) {
+                        for entry in &assertion.diff {
+                            if !error_message.is_empty() {
+                                error_message.push('\n');
+                            }
+                            error_message.push_str(&format!(
+                                "{}: {}",
+                                assertion.expression,
+                                format_diff_entry(entry)
+                            ));
+                        }
+                    }
 
                     xml.push_str(&format!(
                         "      <failure message=\"{}\" type=\"AssertionError\">\n{}\n      </failure>\n",
@@ -264,21 +376,23 @@ impl TapFormatter {
 }
 
 impl OutputFormatter for TapFormatter {
-    fn format(&self, results: &[ExecutionResult], _total_duration: Duration) -> String {
+    fn format(&self, results: &[ExecutionResult], run: &RunInfo) -> String {
         let mut output = String::new();
 
         output.push_str("TAP version 14\n");
         output.push_str(&format!("1..{}\n", results.len()));
+        output.push_str(&format!("# seed: {}\n", run.seed));
 
         for (i, result) in results.iter().enumerate() {
             let test_num = i + 1;
-            let test_name = result
-                .file
-                .file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("unknown");
+            let test_name = result.name();
 
-            if result.failed {
+            if let Some(ref reason) = result.skipped {
+                output.push_str(&format!(
+                    "ok {} - {} # SKIP {}\n",
+                    test_num, test_name, reason
+                ));
+            } else if result.failed {
                 output.push_str(&format!(
                     "not ok {} - {} ({:?})\n",
                     test_num, test_name, result.duration
@@ -293,6 +407,9 @@ impl OutputFormatter for TapFormatter {
                 if let Some(ref error) = result.error {
                     output.push_str(&format!("  error: \"{}\"\n", error));
                 }
+                if !result.attempts.is_empty() {
+                    output.push_str(&format!("  attempts: {}\n", result.attempts.len()));
+                }
                 output.push_str("  ...\n");
             } else {
                 output.push_str(&format!(
@@ -306,6 +423,34 @@ impl OutputFormatter for TapFormatter {
     }
 }
 
+fn count(results: &[ExecutionResult], outcome: Outcome) -> usize {
+    results.iter().filter(|r| r.outcome() == outcome).count()
+}
+
+/// Render a diff entry as `~ /name: "Alice" → "Bob"`, `+ /extra: 1` or
+/// `- /missing: true`
+fn format_diff_entry(entry: &DiffEntry) -> String {
+    let pointer = if entry.pointer.is_empty() {
+        "/"
+    } else {
+        &entry.pointer
+    };
+    let show = |value: &Option<serde_json::Value>| {
+        value.as_ref().map(|v| v.to_string()).unwrap_or_default()
+    };
+
+    match entry.kind {
+        DiffKind::Added => format!("+ {}: {}", pointer, show(&entry.actual)),
+        DiffKind::Removed => format!("- {}: {}", pointer, show(&entry.expected)),
+        DiffKind::Changed => format!(
+            "~ {}: {} → {}",
+            pointer,
+            show(&entry.expected),
+            show(&entry.actual)
+        ),
+    }
+}
+
 fn escape_xml(s: &str) -> String {
     s.replace('&', "&amp;")
         .replace('<', "&lt;")
@@ -313,3 +458,95 @@ fn escape_xml(s: &str) -> String {
         .replace('"', "&quot;")
         .replace('\'', "&apos;")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::AssertionResult;
+    use std::path::Path;
+
+    fn results() -> Vec<ExecutionResult> {
+        let result = |name: &str| {
+            ExecutionResult::not_run(
+                Path::new(name),
+                "GET",
+                "https://api.example.com",
+                Duration::from_millis(5),
+            )
+        };
+
+        let mut passed = result("users.reqx");
+        passed.status = Some(200);
+        let mut failed = result("orders.reqx");
+        failed.status = Some(500);
+        failed.failed = true;
+        failed.assertions.push(AssertionResult {
+            expression: "status".to_string(),
+            expected: "200".to_string(),
+            actual: Some("500".to_string()),
+            passed: false,
+            message: "status: expected = 200, got 500".to_string(),
+            pointer: None,
+            diff: Vec::new(),
+        });
+        // Marked failed as well, but skipped takes precedence
+        let skipped = result("admin.reqx").skipped("env is prod".to_string()).error("x".into());
+
+        vec![passed, failed, skipped]
+    }
+
+    fn run() -> RunInfo {
+        RunInfo {
+            duration: Duration::from_millis(20),
+            seed: 7,
+            now: None,
+        }
+    }
+
+    #[test]
+    fn test_table_counts_skipped() {
+        let output = TableFormatter::new(false).format(&results(), &run());
+        assert!(output.contains("○ GET https://api.example.com SKIP"), "{}", output);
+        assert!(output.contains("Skipped: env is prod"), "{}", output);
+        assert!(
+            output.contains("Total: 3 | Passed: 1 | Failed: 1 | Skipped: 1"),
+            "{}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_json_counts_skipped() {
+        let output = JsonFormatter::new().format(&results(), &run());
+        let json: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(json["summary"]["passed"], 1);
+        assert_eq!(json["summary"]["failed"], 1);
+        assert_eq!(json["summary"]["skipped"], 1);
+
+        let skipped = &json["results"][2];
+        assert_eq!(skipped["passed"], false);
+        assert_eq!(skipped["skipped"], true);
+        assert_eq!(skipped["skip_reason"], "env is prod");
+    }
+
+    #[test]
+    fn test_junit_marks_skipped() {
+        let output = JunitFormatter::new().format(&results(), &run());
+        assert!(
+            output.contains(r#"tests="3" failures="1" errors="0" skipped="1""#),
+            "{}",
+            output
+        );
+        assert!(output.contains(r#"<skipped message="env is prod"/>"#), "{}", output);
+        assert_eq!(output.matches("<failure ").count(), 1, "{}", output);
+    }
+
+    #[test]
+    fn test_tap_marks_skipped() {
+        let output = TapFormatter::new().format(&results(), &run());
+        assert!(output.contains("1..3\n"), "{}", output);
+        assert!(output.contains("ok 1 - users.reqx"), "{}", output);
+        assert!(output.contains("not ok 2 - orders.reqx"), "{}", output);
+        assert!(output.contains("ok 3 - admin.reqx # SKIP env is prod"), "{}", output);
+    }
+}
@@ -26,11 +26,33 @@ pub enum Expression {
         name: String,
         args: Vec<Expression>,
     },
-    /// Pipe expression (e.g., res.body.data | length)
+    /// Pipe expression (e.g., res.body.data | length, res.body.items | map(.id))
     Pipe {
         input: Box<Expression>,
         function: String,
+        args: Vec<PipeArg>,
     },
+    /// Check applied to every value matched by a wildcard path
+    /// (e.g., all body.data[*].id = is_uuid)
+    ForEach {
+        quantifier: Quantifier,
+        check: Box<Expression>,
+    },
+}
+
+impl Expression {
+    /// The path an assertion check is about, e.g. `body.data[*].id` in
+    /// `body.data[*].id | length > 2`
+    pub fn subject_path(&self) -> Option<&PathExpr> {
+        match self {
+            Self::Path(path) => Some(path),
+            Self::BinaryOp { left, .. } => left.subject_path(),
+            Self::FunctionCall { args, .. } => args.first()?.subject_path(),
+            Self::Pipe { input, .. } => input.subject_path(),
+            Self::ForEach { check, .. } => check.subject_path(),
+            Self::Literal(_) | Self::Variable(_) => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -40,6 +62,36 @@ pub enum Literal {
     Float(f64),
     Boolean(bool),
     Null,
+    /// Any other JSON value, such as an array or table from the .reqx file
+    Json(serde_json::Value),
+}
+
+impl Literal {
+    pub fn from_json(value: &serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::String(s) => Self::String(s.clone()),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(i) => Self::Integer(i),
+                None => Self::Float(n.as_f64().unwrap_or_default()),
+            },
+            serde_json::Value::Bool(b) => Self::Boolean(*b),
+            serde_json::Value::Null => Self::Null,
+            other => Self::Json(other.clone()),
+        }
+    }
+}
+
+impl std::fmt::Display for Literal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::String(s) => write!(f, "{}", s),
+            Self::Integer(n) => write!(f, "{}", n),
+            Self::Float(x) => write!(f, "{}", x),
+            Self::Boolean(b) => write!(f, "{}", b),
+            Self::Null => write!(f, "null"),
+            Self::Json(value) => write!(f, "{}", value),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -48,15 +100,78 @@ pub struct PathExpr {
     pub segments: Vec<PathSegment>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
 pub enum PathRoot {
-    Res,      // Response
     Body,     // res.body shorthand
     Headers,  // res.headers shorthand
     Status,   // res.status shorthand
+    Duration, // res.duration, in milliseconds
+    Size,     // res.size, body length in bytes
+    Text,     // res.text, the raw body
+    Cookies,  // res.cookies, values set by Set-Cookie headers
+    Location, // res.location, the parsed Location header
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl PathRoot {
+    /// Roots spelled as plain identifiers rather than keywords
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "duration" => Some(Self::Duration),
+            "size" => Some(Self::Size),
+            "text" => Some(Self::Text),
+            "cookies" => Some(Self::Cookies),
+            "location" => Some(Self::Location),
+            _ => None,
+        }
+    }
+}
+
+/// Measured quantity a path resolves to, compared using units such as
+/// `500ms` or `1MB`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Quantity {
+    Duration,
+    Size,
+}
+
+impl Quantity {
+    /// Unit the resolved value is expressed in
+    pub fn unit(&self) -> &'static str {
+        match self {
+            Self::Duration => "ms",
+            Self::Size => "B",
+        }
+    }
+}
+
+impl PathExpr {
+    /// The quantity this path measures, if any: `duration`, `size` or the
+    /// `content-length` header
+    pub fn quantity(&self) -> Option<Quantity> {
+        match (self.root, self.segments.as_slice()) {
+            (PathRoot::Duration, []) => Some(Quantity::Duration),
+            (PathRoot::Size, []) => Some(Quantity::Size),
+            (PathRoot::Headers, [PathSegment::Property(name)])
+                if name.eq_ignore_ascii_case("content-length") =>
+            {
+                Some(Quantity::Size)
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the path can match several values
+    pub fn is_multi(&self) -> bool {
+        self.segments.iter().any(|segment| {
+            matches!(
+                segment,
+                PathSegment::Wildcard | PathSegment::Query { definite: false, .. }
+            )
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum PathSegment {
     /// Property access (.name)
     Property(String),
@@ -64,6 +179,12 @@ pub enum PathSegment {
     Index(usize),
     /// Wildcard ([*])
     Wildcard,
+    /// Variable key or index ([{{name}}]), resolved before evaluation
+    Variable(String),
+    /// JSONPath query for anything beyond simple paths (filters, `..`,
+    /// slices, negative indices), e.g. `$.items[?(@.active)].id`. A definite
+    /// query selects at most one value; others yield the array of matches.
+    Query { path: String, definite: bool },
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
@@ -107,11 +228,40 @@ impl BinaryOperator {
     }
 }
 
+/// How many of the values matched by a wildcard path must pass a check
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum Quantifier {
+    All,
+    Any,
+    NoneOf,
+}
+
+impl Quantifier {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "all" => Some(Self::All),
+            "any" => Some(Self::Any),
+            "none" => Some(Self::NoneOf),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::All => "all",
+            Self::Any => "any",
+            Self::NoneOf => "none",
+        }
+    }
+}
+
 /// Built-in validation functions
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ValidationFunction {
     Exists,
+    NotExists,
     IsArray,
+    IsObject,
     IsNumber,
     IsString,
     IsUuid,
@@ -123,7 +273,9 @@ impl ValidationFunction {
     pub fn from_str(s: &str) -> Option<Self> {
         match s {
             "exists" => Some(Self::Exists),
+            "!exists" => Some(Self::NotExists),
             "is_array" => Some(Self::IsArray),
+            "is_object" => Some(Self::IsObject),
             "is_number" => Some(Self::IsNumber),
             "is_string" => Some(Self::IsString),
             "is_uuid" => Some(Self::IsUuid),
@@ -132,4 +284,138 @@ impl ValidationFunction {
             _ => None,
         }
     }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Exists => "exists",
+            Self::NotExists => "!exists",
+            Self::IsArray => "is_array",
+            Self::IsObject => "is_object",
+            Self::IsNumber => "is_number",
+            Self::IsString => "is_string",
+            Self::IsUuid => "is_uuid",
+            Self::IsIso8601 => "is_iso8601",
+            Self::IsEmail => "is_email",
+        }
+    }
+}
+
+/// Function validating its first argument against the JSON Schema file named
+/// by its second, from `body = { schema = "schemas/user.json" }`
+pub const SCHEMA_FUNCTION: &str = "schema";
+
+/// Function comparing its argument against the value stored in the request's
+/// snapshot file, from `body = "matches_snapshot"` or a `[snapshot]` section
+pub const SNAPSHOT_FUNCTION: &str = "matches_snapshot";
+
+/// Value computed for a `[pre-request]` variable
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Computed {
+    /// Text with `{{ }}` references, e.g. `{{$uuid}}`
+    Template(String),
+    /// Function applied to computed arguments, e.g.
+    /// `hmac_sha256({{secret}}, {{$timestamp}})`
+    Call {
+        function: String,
+        args: Vec<Computed>,
+    },
+}
+
+/// Functions usable in `[pre-request]` values, with their argument counts
+pub const COMPUTE_FUNCTIONS: &[(&str, usize)] = &[
+    ("sha256", 1),
+    ("hmac_sha256", 2),
+    ("base64", 1),
+    ("base64_decode", 1),
+    ("urlencode", 1),
+    ("upper", 1),
+    ("lower", 1),
+];
+
+/// `[meta] skip_if` / `run_if` condition, e.g. `{{role}} != "admin"` or
+/// `env == "prod"`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Condition {
+    /// True unless the operand is missing, null, false, empty, `"false"` or 0
+    Truthy(Operand),
+    Compare {
+        left: Operand,
+        op: BinaryOperator,
+        right: Operand,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Operand {
+    /// `{{name}}` or `{{$name}}`
+    Variable(String),
+    /// `env`, the name of the selected environment
+    Environment,
+    Literal(Literal),
+}
+
+/// Contents of a `{{ }}` reference, e.g. `{{id ?? other_id | default: "1"}}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Interpolation {
+    /// Candidates tried in order until one has a value
+    pub sources: Vec<InterpolationSource>,
+    /// Filters applied in order to the chosen value
+    pub filters: Vec<InterpolationFilter>,
 }
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum InterpolationSource {
+    /// A variable, dynamic variable (`$uuid`) or environment variable
+    Variable(String),
+    /// A quoted string, e.g. `{{id ?? "1"}}`
+    Literal(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InterpolationFilter {
+    pub name: String,
+    pub args: Vec<String>,
+}
+
+/// Filters usable in `{{ }}` references, with their argument counts
+pub const INTERPOLATION_FILTERS: &[(&str, usize)] = &[
+    ("default", 1),
+    ("upper", 0),
+    ("base64", 0),
+    ("urlencode", 0),
+    ("json", 0),
+];
+
+/// Argument of a pipe function
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PipeArg {
+    /// Path into each element, e.g. `.id` in `map(.id)`
+    Field(Vec<PathSegment>),
+    Literal(Literal),
+}
+
+/// Functions that may appear on the right-hand side of a pipe, with their
+/// minimum and maximum argument counts
+pub const PIPE_FUNCTIONS: &[(&str, usize, usize)] = &[
+    ("length", 0, 0),
+    ("first", 0, 0),
+    ("last", 0, 0),
+    ("filter", 1, 1),
+    ("map", 1, 1),
+    ("join", 0, 1),
+    ("keys", 0, 0),
+    ("values", 0, 0),
+    ("sum", 0, 1),
+    ("min", 0, 1),
+    ("max", 0, 1),
+    ("sort", 0, 1),
+    ("unique", 0, 0),
+    ("reverse", 0, 0),
+    ("lower", 0, 0),
+    ("upper", 0, 0),
+    ("trim", 0, 0),
+    ("split", 1, 1),
+    ("to_number", 0, 0),
+    ("to_string", 0, 0),
+    ("regex", 1, 1),
+];
@@ -0,0 +1,1261 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Expression parser for [assert], [pre-request] and [post-response] entries
+
+use super::ast::{
+    BinaryOperator, Computed, Condition, Expression, Interpolation, InterpolationFilter,
+    InterpolationSource, Literal, Operand, PathExpr, PathRoot, PathSegment, PipeArg, Quantifier,
+    Quantity, ValidationFunction, COMPUTE_FUNCTIONS, INTERPOLATION_FILTERS, PIPE_FUNCTIONS,
+    SCHEMA_FUNCTION, SNAPSHOT_FUNCTION,
+};
+use super::lexer::{tokenize, LexError, Token};
+use jsonpath_rust::JsonPathInst;
+use serde_json::Value;
+use std::sync::OnceLock;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExpressionError {
+    #[error(transparent)]
+    Lex(#[from] LexError),
+
+    #[error("unexpected end of expression")]
+    UnexpectedEnd,
+
+    #[error("unexpected token {0:?}")]
+    UnexpectedToken(Token),
+
+    #[error(
+        "unknown root '{0}', expected status, body, headers, duration, size, text, cookies, \
+         location or res.*"
+    )]
+    UnknownRoot(String),
+
+    #[error("unknown function '{0}'")]
+    UnknownFunction(String),
+
+    #[error("invalid regex '{0}': {1}")]
+    InvalidRegex(String, regex::Error),
+
+    #[error("missing value after '{0}'")]
+    MissingOperand(&'static str),
+
+    #[error("invalid JSONPath '{0}': {1}")]
+    InvalidJsonPath(String, String),
+
+    #[error("{function} takes {expected} argument(s), found {found}")]
+    ArgumentCount {
+        function: String,
+        expected: usize,
+        found: usize,
+    },
+
+    #[error("expected a comparison such as 'body.status = done'")]
+    MissingComparison,
+
+    #[error("invalid {0} '{1}', expected a number with a unit such as {2}")]
+    InvalidQuantity(&'static str, String, &'static str),
+}
+
+/// Parse a standalone expression such as `res.body.data | length`.
+///
+/// Body paths that the simple grammar cannot express (`body..id`,
+/// `body.items[-1]`, `$.items[?(@.active)]`) are handed to JSONPath.
+pub fn parse_expression(input: &str) -> Result<Expression, ExpressionError> {
+    match parse_simple(input) {
+        Ok(expression) => Ok(expression),
+        Err(err) => parse_json_path(input).unwrap_or(Err(err)),
+    }
+}
+
+fn parse_simple(input: &str) -> Result<Expression, ExpressionError> {
+    let mut parser = Parser::new(tokenize(input)?);
+    let expression = parser.parse_pipeline()?;
+    parser.finish(expression)
+}
+
+/// Parse a body path as a JSONPath query. Returns `None` when the input does
+/// not start with a body root, so the simple grammar's error is reported.
+fn parse_json_path(input: &str) -> Option<Result<Expression, ExpressionError>> {
+    let (head, pipes) = split_pipeline(input);
+    let head = head.trim();
+
+    let query = if head.starts_with('$') {
+        head.to_string()
+    } else {
+        let rest = head
+            .strip_prefix("res.body")
+            .or_else(|| head.strip_prefix("body"))?;
+        if !rest.starts_with(['.', '[']) {
+            return None;
+        }
+        format!("${}", rest)
+    };
+
+    Some(parse_query(query, pipes))
+}
+
+fn parse_query(query: String, pipes: &str) -> Result<Expression, ExpressionError> {
+    let definite = is_definite(&query);
+
+    // The engine only accepts negative positions in slices, so `[-2]` becomes `[-2:-1]`
+    static NEGATIVE_INDEX: OnceLock<regex::Regex> = OnceLock::new();
+    let negative_index =
+        NEGATIVE_INDEX.get_or_init(|| regex::Regex::new(r"\[(-[0-9]+)\]").unwrap());
+    let path = negative_index
+        .replace_all(&query, |caps: &regex::Captures| {
+            let index: i64 = caps[1].parse().unwrap_or(-1);
+            match index + 1 {
+                0 => format!("[{}:]", index),
+                end => format!("[{}:{}]", index, end),
+            }
+        })
+        .into_owned();
+
+    path.parse::<JsonPathInst>()
+        .map_err(|e| ExpressionError::InvalidJsonPath(query.clone(), e))?;
+
+    let expression = Expression::Path(PathExpr {
+        root: PathRoot::Body,
+        segments: vec![PathSegment::Query { path, definite }],
+    });
+
+    let mut parser = Parser::new(tokenize(pipes)?);
+    let expression = parser.parse_pipes(expression)?;
+    parser.finish(expression)
+}
+
+/// Whether a JSONPath query can select at most one value: no wildcards,
+/// recursive descent, filters, slices or unions
+fn is_definite(query: &str) -> bool {
+    let mut in_brackets = false;
+    let mut previous = ' ';
+
+    for c in query.chars() {
+        match c {
+            '[' => in_brackets = true,
+            ']' => in_brackets = false,
+            '*' => return false,
+            '.' if previous == '.' => return false,
+            '?' | ':' | ',' if in_brackets => return false,
+            _ => {}
+        }
+        previous = c;
+    }
+
+    true
+}
+
+/// Parse a `[pre-request]` value: either a template such as `{{$uuid}}` or
+/// a call such as `hmac_sha256({{secret}}, "{{method}} {{path}}")`, whose
+/// arguments may be quoted and may be calls themselves.
+pub fn parse_computed(input: &str) -> Result<Computed, ExpressionError> {
+    let input = input.trim();
+
+    let call = input
+        .strip_suffix(')')
+        .and_then(|rest| rest.split_once('('))
+        .filter(|(name, _)| {
+            !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        });
+
+    let Some((name, args)) = call else {
+        return Ok(Computed::Template(unquote(input).to_string()));
+    };
+
+    let expected = COMPUTE_FUNCTIONS
+        .iter()
+        .find(|(function, _)| *function == name)
+        .map(|(_, arity)| *arity)
+        .ok_or_else(|| ExpressionError::UnknownFunction(name.to_string()))?;
+
+    let args = split_top_level(args, ",")
+        .into_iter()
+        .filter(|arg| !arg.trim().is_empty())
+        .map(parse_computed)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if args.len() != expected {
+        return Err(ExpressionError::ArgumentCount {
+            function: name.to_string(),
+            expected,
+            found: args.len(),
+        });
+    }
+
+    Ok(Computed::Call {
+        function: name.to_string(),
+        args,
+    })
+}
+
+/// Parse a `[meta] skip_if` or `run_if` condition: one operand, or two
+/// compared with `==`, `!=`, `<`, `>`, `<=`, `>=`, `contains` or `matches`
+pub fn parse_condition(input: &str) -> Result<Condition, ExpressionError> {
+    let mut parser = Parser::new(tokenize(input)?);
+    let left = parser.parse_operand()?;
+
+    let op = match parser.next() {
+        None => return Ok(Condition::Truthy(left)),
+        Some(Token::Equals) => {
+            // Accept `==` as well as `=`
+            if parser.peek() == Some(&Token::Equals) {
+                parser.next();
+            }
+            BinaryOperator::Equals
+        }
+        Some(Token::NotEquals) => BinaryOperator::NotEquals,
+        Some(Token::LessThan) => BinaryOperator::LessThan,
+        Some(Token::GreaterThan) => BinaryOperator::GreaterThan,
+        Some(Token::LessOrEqual) => BinaryOperator::LessOrEqual,
+        Some(Token::GreaterOrEqual) => BinaryOperator::GreaterOrEqual,
+        Some(Token::Contains) => BinaryOperator::Contains,
+        Some(Token::Matches) => BinaryOperator::Matches,
+        Some(token) => return Err(ExpressionError::UnexpectedToken(token)),
+    };
+    let right = parser.parse_operand()?;
+
+    if let Some(token) = parser.next() {
+        return Err(ExpressionError::UnexpectedToken(token));
+    }
+    if let (BinaryOperator::Matches, Operand::Literal(Literal::String(pattern))) = (op, &right) {
+        regex::Regex::new(pattern)
+            .map_err(|e| ExpressionError::InvalidRegex(pattern.clone(), e))?;
+    }
+
+    Ok(Condition::Compare { left, op, right })
+}
+
+/// Parse a `[retry] until` condition such as `body.status = done`. It reads
+/// like an `[assert]` entry whose key and value are written together, so
+/// `body.items | length >= 3` checks the same as `"body.items | length" = ">= 3"`.
+pub fn parse_until(input: &str) -> Result<Expression, ExpressionError> {
+    let (subject, expected) = split_comparison(input).ok_or(ExpressionError::MissingComparison)?;
+    if subject.trim().is_empty() {
+        return Err(ExpressionError::MissingComparison);
+    }
+    // The operator may be written against its operand, as in `length>=3`
+    let operator_len = expected
+        .find(|c| !matches!(c, '<' | '>' | '=' | '!'))
+        .unwrap_or(expected.len());
+    let (operator, operand) = expected.split_at(operator_len);
+    let expected = format!("{} {}", operator, operand.trim());
+    parse_assertion(subject, &Value::String(expected.trim().to_string()))
+}
+
+/// Parse a duration such as `500ms`, `2s`, `1m` or `12h` into milliseconds
+pub fn parse_duration(input: &str) -> Result<u64, ExpressionError> {
+    match quantity_literal(Quantity::Duration, input.trim())? {
+        Literal::Integer(ms) if ms >= 0 => Ok(ms as u64),
+        Literal::Float(ms) if ms >= 0.0 => Ok(ms.round() as u64),
+        _ => Err(ExpressionError::InvalidQuantity(
+            "duration",
+            input.to_string(),
+            "500ms or 2s",
+        )),
+    }
+}
+
+/// Parse the text between `{{` and `}}`: one or more sources separated by
+/// `??`, followed by filters such as `| upper` or `| default: "1"`
+pub fn parse_interpolation(input: &str) -> Result<Interpolation, ExpressionError> {
+    let mut stages = split_top_level(input, "|").into_iter();
+    let source = stages.next().unwrap_or_default();
+
+    let sources = split_top_level(source, "??")
+        .into_iter()
+        .map(|source| {
+            let source = source.trim();
+            if source.is_empty() {
+                Err(ExpressionError::MissingOperand("??"))
+            } else if unquote(source).len() != source.len() {
+                Ok(InterpolationSource::Literal(unquote(source).to_string()))
+            } else {
+                Ok(InterpolationSource::Variable(source.to_string()))
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let filters = stages
+        .map(parse_interpolation_filter)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Interpolation { sources, filters })
+}
+
+/// Parse `name`, `name: arg, ...` or `name(arg, ...)`
+fn parse_interpolation_filter(input: &str) -> Result<InterpolationFilter, ExpressionError> {
+    let input = input.trim();
+    let split = input.find([':', '(']);
+
+    let (name, args) = match split {
+        Some(i) if input.as_bytes()[i] == b'(' => {
+            let args = input[i + 1..]
+                .strip_suffix(')')
+                .ok_or(ExpressionError::UnexpectedEnd)?;
+            (&input[..i], args)
+        }
+        Some(i) => (&input[..i], &input[i + 1..]),
+        None => (input, ""),
+    };
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(ExpressionError::MissingOperand("|"));
+    }
+
+    let expected = INTERPOLATION_FILTERS
+        .iter()
+        .find(|(filter, _)| *filter == name)
+        .map(|(_, arity)| *arity)
+        .ok_or_else(|| ExpressionError::UnknownFunction(name.to_string()))?;
+
+    let args: Vec<String> = split_top_level(args, ",")
+        .into_iter()
+        .map(str::trim)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| unquote(arg).to_string())
+        .collect();
+
+    if args.len() != expected {
+        return Err(ExpressionError::ArgumentCount {
+            function: name.to_string(),
+            expected,
+            found: args.len(),
+        });
+    }
+
+    Ok(InterpolationFilter {
+        name: name.to_string(),
+        args,
+    })
+}
+
+fn unquote(input: &str) -> &str {
+    for quote in ['"', '\''] {
+        if let Some(inner) = input
+            .strip_prefix(quote)
+            .and_then(|rest| rest.strip_suffix(quote))
+        {
+            return inner;
+        }
+    }
+    input
+}
+
+/// Split on a separator outside brackets, parentheses and quotes
+fn split_top_level<'a>(input: &'a str, separator: &str) -> Vec<&'a str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut quote = None;
+    let mut start = 0;
+    let mut skip = 0;
+
+    for (i, &c) in input.as_bytes().iter().enumerate() {
+        if skip > 0 {
+            skip -= 1;
+            continue;
+        }
+        match (quote, c) {
+            (Some(q), _) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, b'\'' | b'"') => quote = Some(c),
+            (None, b'[' | b'(') => depth += 1,
+            (None, b']' | b')') => depth = depth.saturating_sub(1),
+            (None, _) if depth == 0 && input.as_bytes()[i..].starts_with(separator.as_bytes()) => {
+                parts.push(&input[start..i]);
+                start = i + separator.len();
+                skip = separator.len() - 1;
+            }
+            _ => {}
+        }
+    }
+
+    parts.push(&input[start..]);
+    parts
+}
+
+/// Split a condition before its first top-level comparison operator, so
+/// the second part starts with the operator
+fn split_comparison(input: &str) -> Option<(&str, &str)> {
+    let bytes = input.as_bytes();
+    let mut depth = 0usize;
+    let mut quote = None;
+
+    for (i, &c) in bytes.iter().enumerate() {
+        match (quote, c) {
+            (Some(q), _) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, b'\'' | b'"') => quote = Some(c),
+            (None, b'[' | b'(') => depth += 1,
+            (None, b']' | b')') => depth = depth.saturating_sub(1),
+            (None, b'!') if depth == 0 && bytes.get(i + 1) == Some(&b'=') => {
+                return Some((&input[..i], &input[i..]));
+            }
+            (None, b'=' | b'<' | b'>') if depth == 0 => {
+                return Some((&input[..i], &input[i..]));
+            }
+            (None, b' ') if depth == 0 => {
+                let rest = &input[i + 1..];
+                if rest.starts_with("contains ") || rest.starts_with("matches ") {
+                    return Some((&input[..i], rest));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Split an expression at its first top-level pipe, ignoring `|` inside
+/// brackets, parentheses, quotes and `||` operators of JSONPath filters
+fn split_pipeline(input: &str) -> (&str, &str) {
+    let bytes = input.as_bytes();
+    let mut depth = 0usize;
+    let mut quote = None;
+
+    for (i, &c) in bytes.iter().enumerate() {
+        match (quote, c) {
+            (Some(q), _) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, b'\'' | b'"') => quote = Some(c),
+            (None, b'[' | b'(') => depth += 1,
+            (None, b']' | b')') => depth = depth.saturating_sub(1),
+            (None, b'|') if depth == 0 => {
+                let doubled = bytes.get(i + 1) == Some(&b'|') || (i > 0 && bytes[i - 1] == b'|');
+                if !doubled {
+                    return (&input[..i], &input[i..]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    (input, "")
+}
+
+/// Parse an [assert] entry into a check expression.
+///
+/// The key is the subject of the assertion. A non-string value (integer,
+/// float, bool, array, inline table) must equal the subject with the same JSON
+/// type. A string value is either a validation function (`exists`, `is_uuid`,
+/// ...), a comparison with a leading operator (`> 10`, `!= draft`,
+/// `contains json`, `matches ^[a-z]+@`) or the string the subject must equal.
+/// Prefix a string with `=` to compare it literally.
+///
+/// When the key matches several values (`body.data[*].id`) the check applies
+/// to each of them. By default all must pass; prefix the key with `any` or
+/// `none` to require at least one or none of them to pass instead.
+pub fn parse_assertion(key: &str, expected: &Value) -> Result<Expression, ExpressionError> {
+    let (quantifier, key) = match key.trim().split_once(char::is_whitespace) {
+        Some((word, rest)) => match Quantifier::from_str(word) {
+            Some(quantifier) => (Some(quantifier), rest),
+            None => (None, key),
+        },
+        None => (None, key),
+    };
+
+    let check = parse_check(parse_expression(key)?, expected)?;
+    // A snapshot covers every match at once rather than each in turn
+    let multi =
+        check.subject_path().is_some_and(|path| path.is_multi()) && !is_snapshot_check(&check);
+
+    Ok(match quantifier {
+        Some(quantifier) => Expression::ForEach {
+            quantifier,
+            check: Box::new(check),
+        },
+        None if multi => Expression::ForEach {
+            quantifier: Quantifier::All,
+            check: Box::new(check),
+        },
+        None => check,
+    })
+}
+
+fn parse_check(subject: Expression, expected: &Value) -> Result<Expression, ExpressionError> {
+    if let Some(schema) = schema_reference(expected) {
+        return Ok(Expression::FunctionCall {
+            name: SCHEMA_FUNCTION.to_string(),
+            args: vec![
+                subject,
+                Expression::Literal(Literal::String(schema.to_string())),
+            ],
+        });
+    }
+
+    if expected.as_str().map(str::trim) == Some(SNAPSHOT_FUNCTION) {
+        return Ok(Expression::FunctionCall {
+            name: SNAPSHOT_FUNCTION.to_string(),
+            args: vec![subject],
+        });
+    }
+
+    let Some(expected) = expected.as_str() else {
+        return Ok(Expression::BinaryOp {
+            left: Box::new(subject),
+            op: BinaryOperator::Equals,
+            right: Box::new(Expression::Literal(Literal::from_json(expected))),
+        });
+    };
+    let expected = expected.trim();
+
+    if let Some(validation) = ValidationFunction::from_str(expected) {
+        return Ok(Expression::FunctionCall {
+            name: validation.as_str().to_string(),
+            args: vec![subject],
+        });
+    }
+
+    let (op, operand, explicit) = split_operator(expected);
+    if operand.is_empty() && explicit {
+        return Err(ExpressionError::MissingOperand(op.as_str()));
+    }
+
+    let quantity = match &subject {
+        Expression::Path(path) => path.quantity(),
+        _ => None,
+    };
+
+    let literal = match (op, quantity) {
+        (BinaryOperator::Matches, _) => {
+            regex::Regex::new(operand)
+                .map_err(|e| ExpressionError::InvalidRegex(operand.to_string(), e))?;
+            Literal::String(operand.to_string())
+        }
+        (BinaryOperator::Contains, _) => scalar_literal(operand),
+        (_, Some(quantity)) => quantity_literal(quantity, operand)?,
+        _ if explicit => scalar_literal(operand),
+        _ => Literal::String(operand.to_string()),
+    };
+
+    Ok(Expression::BinaryOp {
+        left: Box::new(subject),
+        op,
+        right: Box::new(Expression::Literal(literal)),
+    })
+}
+
+/// Whether a parsed assertion compares against the stored snapshot
+pub fn is_snapshot_check(expression: &Expression) -> bool {
+    matches!(expression, Expression::FunctionCall { name, .. } if name == SNAPSHOT_FUNCTION)
+}
+
+/// The schema file named by a `{ schema = "..." }` assertion value
+pub fn schema_reference(expected: &Value) -> Option<&str> {
+    match expected.as_object() {
+        Some(table) if table.len() == 1 => table.get("schema")?.as_str(),
+        _ => None,
+    }
+}
+
+/// Split a leading comparison operator off an assertion value. A symbolic
+/// operator must be followed by whitespace, so `"<html>"` is a literal and
+/// `"< 10"` a comparison. The flag is false when the value has no operator
+/// and is compared as-is.
+fn split_operator(value: &str) -> (BinaryOperator, &str, bool) {
+    for symbol in [">=", "<=", "!=", "==", ">", "<", "="] {
+        let Some(rest) = value.strip_prefix(symbol) else {
+            continue;
+        };
+        if rest.is_empty() || rest.starts_with(char::is_whitespace) {
+            if let Some(op) = BinaryOperator::from_str(symbol) {
+                return (op, rest.trim_start(), true);
+            }
+        }
+    }
+
+    if let Some((word, rest)) = value.split_once(char::is_whitespace) {
+        if let Some(op @ (BinaryOperator::Contains | BinaryOperator::Matches)) =
+            BinaryOperator::from_str(word)
+        {
+            return (op, rest.trim_start(), true);
+        }
+    }
+
+    (BinaryOperator::Equals, value, false)
+}
+
+/// Convert a duration (`500ms`, `2s`, `1m`, `1h`) to milliseconds or a size
+/// (`512B`, `10KB`, `1MB`, `1GB`, 1024-based) to bytes. A bare number is
+/// already in those units.
+fn quantity_literal(quantity: Quantity, operand: &str) -> Result<Literal, ExpressionError> {
+    let split = operand
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(operand.len());
+    let (number, unit) = operand.split_at(split);
+
+    let scale = match (quantity, unit.trim().to_ascii_lowercase().as_str()) {
+        (Quantity::Duration, "" | "ms") => Some(1.0),
+        (Quantity::Duration, "s") => Some(1_000.0),
+        (Quantity::Duration, "m") => Some(60_000.0),
+        (Quantity::Duration, "h") => Some(3_600_000.0),
+        (Quantity::Size, "" | "b") => Some(1.0),
+        (Quantity::Size, "kb") => Some(1024.0),
+        (Quantity::Size, "mb") => Some(1024.0 * 1024.0),
+        (Quantity::Size, "gb") => Some(1024.0 * 1024.0 * 1024.0),
+        _ => None,
+    };
+
+    match (number.parse::<f64>(), scale) {
+        (Ok(number), Some(scale)) => {
+            let value = number * scale;
+            Ok(if value.fract() == 0.0 {
+                Literal::Integer(value as i64)
+            } else {
+                Literal::Float(value)
+            })
+        }
+        _ => Err(match quantity {
+            Quantity::Duration => {
+                ExpressionError::InvalidQuantity("duration", operand.to_string(), "500ms or 2s")
+            }
+            Quantity::Size => {
+                ExpressionError::InvalidQuantity("size", operand.to_string(), "512B or 1MB")
+            }
+        }),
+    }
+}
+
+/// Type an operand written after an operator, so `"!= 3"` compares against
+/// the number 3 and `"= true"` against the boolean
+fn scalar_literal(operand: &str) -> Literal {
+    if let Ok(n) = operand.parse::<i64>() {
+        Literal::Integer(n)
+    } else if let Ok(f) = operand.parse::<f64>() {
+        Literal::Float(f)
+    } else {
+        match operand {
+            "true" => Literal::Boolean(true),
+            "false" => Literal::Boolean(false),
+            "null" => Literal::Null,
+            _ => Literal::String(operand.to_string()),
+        }
+    }
+}
+
+fn path_root(token: &Token) -> Option<PathRoot> {
+    match token {
+        Token::Status => Some(PathRoot::Status),
+        Token::Body => Some(PathRoot::Body),
+        Token::Headers => Some(PathRoot::Headers),
+        Token::Identifier(name) => PathRoot::from_name(name),
+        _ => None,
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(tokens: Vec<Token>) -> Self {
+        Self { tokens, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        if token.is_some() {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), ExpressionError> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(ExpressionError::UnexpectedToken(token)),
+            None => Err(ExpressionError::UnexpectedEnd),
+        }
+    }
+
+    fn parse_name(&mut self) -> Result<String, ExpressionError> {
+        match self.next() {
+            Some(token) => match token.as_name() {
+                Some(name) => Ok(name.to_string()),
+                None => Err(ExpressionError::UnexpectedToken(token)),
+            },
+            None => Err(ExpressionError::UnexpectedEnd),
+        }
+    }
+
+    /// Parse the rest of a `{{name}}` or `{{$name}}` reference after its opening braces
+    fn parse_variable(&mut self) -> Result<String, ExpressionError> {
+        let dynamic = self.peek() == Some(&Token::Dollar);
+        if dynamic {
+            self.next();
+        }
+        let name = self.parse_name()?;
+        self.expect(Token::VariableEnd)?;
+
+        Ok(if dynamic { format!("${}", name) } else { name })
+    }
+
+    /// Parse a condition operand: a variable, `env` or a literal
+    fn parse_operand(&mut self) -> Result<Operand, ExpressionError> {
+        match self.next().ok_or(ExpressionError::UnexpectedEnd)? {
+            Token::VariableStart => Ok(Operand::Variable(self.parse_variable()?)),
+            Token::Identifier(name) if name == "env" => Ok(Operand::Environment),
+            Token::Identifier(name) if name == "null" => Ok(Operand::Literal(Literal::Null)),
+            Token::String(s) => Ok(Operand::Literal(Literal::String(s))),
+            Token::Integer(n) => Ok(Operand::Literal(Literal::Integer(n))),
+            Token::Float(f) => Ok(Operand::Literal(Literal::Float(f))),
+            Token::Boolean(b) => Ok(Operand::Literal(Literal::Boolean(b))),
+            other => Err(ExpressionError::UnexpectedToken(other)),
+        }
+    }
+
+    /// Fail if any tokens are left after a complete expression
+    fn finish(&mut self, expression: Expression) -> Result<Expression, ExpressionError> {
+        match self.next() {
+            Some(token) => Err(ExpressionError::UnexpectedToken(token)),
+            None => Ok(expression),
+        }
+    }
+
+    fn parse_pipeline(&mut self) -> Result<Expression, ExpressionError> {
+        let expression = self.parse_primary()?;
+        self.parse_pipes(expression)
+    }
+
+    fn parse_pipes(&mut self, mut expression: Expression) -> Result<Expression, ExpressionError> {
+        while self.peek() == Some(&Token::Pipe) {
+            self.next();
+            let function = self.parse_name()?;
+            let (min, max) = PIPE_FUNCTIONS
+                .iter()
+                .find(|(name, ..)| *name == function)
+                .map(|(_, min, max)| (*min, *max))
+                .ok_or_else(|| ExpressionError::UnknownFunction(function.clone()))?;
+
+            let args = if self.peek() == Some(&Token::LeftParen) {
+                self.next();
+                self.parse_pipe_args()?
+            } else {
+                Vec::new()
+            };
+            if args.len() < min || args.len() > max {
+                return Err(ExpressionError::ArgumentCount {
+                    function,
+                    expected: if args.len() < min { min } else { max },
+                    found: args.len(),
+                });
+            }
+            if let ("regex", [PipeArg::Literal(Literal::String(pattern))]) =
+                (function.as_str(), &args[..])
+            {
+                regex::Regex::new(pattern)
+                    .map_err(|e| ExpressionError::InvalidRegex(pattern.clone(), e))?;
+            }
+
+            expression = Expression::Pipe {
+                input: Box::new(expression),
+                function,
+                args,
+            };
+        }
+
+        Ok(expression)
+    }
+
+    /// Parse pipe function arguments up to the closing parenthesis: fields
+    /// such as `.id` or literals such as `","`
+    fn parse_pipe_args(&mut self) -> Result<Vec<PipeArg>, ExpressionError> {
+        let mut args = Vec::new();
+
+        loop {
+            let arg = match self.peek() {
+                Some(Token::RightParen) if args.is_empty() => {
+                    self.next();
+                    return Ok(args);
+                }
+                Some(Token::Dot) => PipeArg::Field(self.parse_segments()?),
+                _ => match self.next() {
+                    Some(Token::String(s)) => PipeArg::Literal(Literal::String(s)),
+                    Some(Token::Integer(n)) => PipeArg::Literal(Literal::Integer(n)),
+                    Some(Token::Float(f)) => PipeArg::Literal(Literal::Float(f)),
+                    Some(Token::Boolean(b)) => PipeArg::Literal(Literal::Boolean(b)),
+                    Some(token) => return Err(ExpressionError::UnexpectedToken(token)),
+                    None => return Err(ExpressionError::UnexpectedEnd),
+                },
+            };
+            args.push(arg);
+
+            match self.next() {
+                Some(Token::Comma) => {}
+                Some(Token::RightParen) => return Ok(args),
+                Some(token) => return Err(ExpressionError::UnexpectedToken(token)),
+                None => return Err(ExpressionError::UnexpectedEnd),
+            }
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expression, ExpressionError> {
+        let token = self.next().ok_or(ExpressionError::UnexpectedEnd)?;
+
+        let root = match token {
+            Token::String(s) => return Ok(Expression::Literal(Literal::String(s))),
+            Token::Integer(n) => return Ok(Expression::Literal(Literal::Integer(n))),
+            Token::Float(f) => return Ok(Expression::Literal(Literal::Float(f))),
+            Token::Boolean(b) => return Ok(Expression::Literal(Literal::Boolean(b))),
+            Token::Identifier(ref name) if name == "null" => {
+                return Ok(Expression::Literal(Literal::Null))
+            }
+            Token::VariableStart => return Ok(Expression::Variable(self.parse_variable()?)),
+            Token::Res => {
+                self.expect(Token::Dot)?;
+                match self.next() {
+                    Some(token) => path_root(&token).ok_or_else(|| {
+                        let name = token.as_name().unwrap_or_default();
+                        ExpressionError::UnknownRoot(format!("res.{}", name))
+                    })?,
+                    None => return Err(ExpressionError::UnexpectedEnd),
+                }
+            }
+            other => match (path_root(&other), other.as_name()) {
+                (Some(root), _) => root,
+                (None, Some(name)) => return Err(ExpressionError::UnknownRoot(name.to_string())),
+                (None, None) => return Err(ExpressionError::UnexpectedToken(other)),
+            },
+        };
+
+        let segments = self.parse_segments()?;
+        Ok(Expression::Path(PathExpr { root, segments }))
+    }
+
+    fn parse_segments(&mut self) -> Result<Vec<PathSegment>, ExpressionError> {
+        let mut segments = Vec::new();
+
+        loop {
+            match self.peek() {
+                Some(Token::Dot) => {
+                    self.next();
+                    let segment = if self.peek() == Some(&Token::VariableStart) {
+                        self.next();
+                        PathSegment::Variable(self.parse_variable()?)
+                    } else {
+                        PathSegment::Property(self.parse_name()?)
+                    };
+                    segments.push(segment);
+                }
+                Some(Token::LeftBracket) => {
+                    self.next();
+                    let segment = match self.next() {
+                        Some(Token::Integer(n)) if n >= 0 => PathSegment::Index(n as usize),
+                        Some(Token::Star) => PathSegment::Wildcard,
+                        Some(Token::String(name)) => PathSegment::Property(name),
+                        Some(Token::VariableStart) => PathSegment::Variable(self.parse_variable()?),
+                        Some(token) => return Err(ExpressionError::UnexpectedToken(token)),
+                        None => return Err(ExpressionError::UnexpectedEnd),
+                    };
+                    self.expect(Token::RightBracket)?;
+                    segments.push(segment);
+                }
+                _ => break,
+            }
+        }
+
+        Ok(segments)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_body_path() {
+        let expr = parse_expression("body.data[0].id").unwrap();
+        match expr {
+            Expression::Path(path) => {
+                assert_eq!(path.root, PathRoot::Body);
+                assert_eq!(
+                    path.segments,
+                    vec![
+                        PathSegment::Property("data".to_string()),
+                        PathSegment::Index(0),
+                        PathSegment::Property("id".to_string()),
+                    ]
+                );
+            }
+            other => panic!("expected path, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_res_prefix_and_pipe() {
+        let expr = parse_expression("res.body.items | length").unwrap();
+        match expr {
+            Expression::Pipe {
+                input, function, ..
+            } => {
+                assert_eq!(function, "length");
+                assert!(matches!(
+                    *input,
+                    Expression::Path(PathExpr {
+                        root: PathRoot::Body,
+                        ..
+                    })
+                ));
+            }
+            other => panic!("expected pipe, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_assertion_validation() {
+        let expr = parse_assertion("body.id", &Value::from("is_uuid")).unwrap();
+        assert!(matches!(expr, Expression::FunctionCall { ref name, .. } if name == "is_uuid"));
+    }
+
+    #[test]
+    fn test_parse_quantity_units() {
+        for (key, expected, millis) in [
+            ("duration", "< 500ms", 500),
+            ("duration", "< 2s", 2000),
+            ("res.duration", "<= 1.5s", 1500),
+            ("size", "< 1MB", 1024 * 1024),
+            ("headers.content-length", "> 2KB", 2048),
+        ] {
+            let expr = parse_assertion(key, &Value::from(expected)).unwrap();
+            assert!(
+                matches!(
+                    expr,
+                    Expression::BinaryOp { ref right, .. }
+                        if matches!(**right, Expression::Literal(Literal::Integer(n)) if n == millis)
+                ),
+                "{} {}",
+                key,
+                expected
+            );
+        }
+
+        let err = parse_assertion("duration", &Value::from("< 5 parsecs")).unwrap_err();
+        assert!(matches!(
+            err,
+            ExpressionError::InvalidQuantity("duration", ..)
+        ));
+    }
+
+    #[test]
+    fn test_parse_assertion_operators() {
+        let expr = parse_assertion("body.total", &Value::from("> 10")).unwrap();
+        assert!(matches!(
+            expr,
+            Expression::BinaryOp {
+                op: BinaryOperator::GreaterThan,
+                ref right,
+                ..
+            } if matches!(**right, Expression::Literal(Literal::Integer(10)))
+        ));
+
+        let expr = parse_assertion("headers.content-type", &Value::from("contains json")).unwrap();
+        assert!(matches!(
+            expr,
+            Expression::BinaryOp {
+                op: BinaryOperator::Contains,
+                ..
+            }
+        ));
+
+        let expr = parse_assertion("body.name", &Value::from("= <b>bold</b>")).unwrap();
+        assert!(matches!(
+            expr,
+            Expression::BinaryOp {
+                op: BinaryOperator::Equals,
+                ref right,
+                ..
+            } if matches!(**right, Expression::Literal(Literal::String(ref s)) if s == "<b>bold</b>")
+        ));
+
+        // Without whitespace after it, a leading symbol is part of the literal
+        let expr = parse_assertion("body.html", &Value::from("<html>")).unwrap();
+        assert!(matches!(
+            expr,
+            Expression::BinaryOp {
+                op: BinaryOperator::Equals,
+                ref right,
+                ..
+            } if matches!(**right, Expression::Literal(Literal::String(ref s)) if s == "<html>")
+        ));
+        assert!(matches!(
+            parse_assertion("body.note", &Value::from("=>")).unwrap(),
+            Expression::BinaryOp {
+                op: BinaryOperator::Equals,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_parse_assertion_keeps_value_type() {
+        let expr = parse_assertion("body.id", &Value::from(1)).unwrap();
+        assert!(matches!(
+            expr,
+            Expression::BinaryOp { ref right, .. }
+                if matches!(**right, Expression::Literal(Literal::Integer(1)))
+        ));
+
+        let expr = parse_assertion("body.id", &Value::from("1")).unwrap();
+        assert!(matches!(
+            expr,
+            Expression::BinaryOp { ref right, .. }
+                if matches!(**right, Expression::Literal(Literal::String(ref s)) if s == "1")
+        ));
+    }
+
+    #[test]
+    fn test_parse_assertion_quantifiers() {
+        let expr = parse_assertion("body.data[*].id", &Value::from("is_uuid")).unwrap();
+        assert!(matches!(
+            expr,
+            Expression::ForEach {
+                quantifier: Quantifier::All,
+                ..
+            }
+        ));
+
+        let expr = parse_assertion("any body.tags[*]", &Value::from("urgent")).unwrap();
+        assert!(matches!(
+            expr,
+            Expression::ForEach {
+                quantifier: Quantifier::Any,
+                ..
+            }
+        ));
+
+        let expr = parse_assertion("body.data[0].id", &Value::from("is_uuid")).unwrap();
+        assert!(matches!(expr, Expression::FunctionCall { .. }));
+    }
+
+    #[test]
+    fn test_parse_assertion_rejects_invalid_regex() {
+        let err = parse_assertion("body.email", &Value::from("matches ^[a-z+@")).unwrap_err();
+        assert!(matches!(err, ExpressionError::InvalidRegex(..)));
+    }
+
+    #[test]
+    fn test_parse_variables() {
+        let expr = parse_expression("body.users[{{index}}].{{field}}").unwrap();
+        match expr {
+            Expression::Path(path) => assert_eq!(
+                path.segments,
+                vec![
+                    PathSegment::Property("users".to_string()),
+                    PathSegment::Variable("index".to_string()),
+                    PathSegment::Variable("field".to_string()),
+                ]
+            ),
+            other => panic!("expected path, got {:?}", other),
+        }
+
+        let expr = parse_expression("{{$timestamp}}").unwrap();
+        assert!(matches!(expr, Expression::Variable(ref name) if name == "$timestamp"));
+    }
+
+    #[test]
+    fn test_parse_json_path() {
+        let cases = [
+            ("body..id", "$..id", false),
+            ("body.items[-1].id", "$.items[-1:].id", true),
+            ("body.items[-2]", "$.items[-2:-1]", true),
+            ("res.body.items[0:2]", "$.items[0:2]", false),
+            (
+                "$.items[?(@.status=='active')].id",
+                "$.items[?(@.status=='active')].id",
+                false,
+            ),
+        ];
+
+        for (input, query, definite) in cases {
+            match parse_expression(input).unwrap() {
+                Expression::Path(path) => assert_eq!(
+                    path.segments,
+                    vec![PathSegment::Query {
+                        path: query.to_string(),
+                        definite
+                    }]
+                ),
+                other => panic!("expected path for {}, got {:?}", input, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_json_path_with_pipe() {
+        let expr = parse_expression("body.items[?(@.a || @.b)] | length").unwrap();
+        match expr {
+            Expression::Pipe {
+                input, function, ..
+            } => {
+                assert_eq!(function, "length");
+                assert!(matches!(*input, Expression::Path(_)));
+            }
+            other => panic!("expected pipe, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_json_path() {
+        let err = parse_expression("body.items[?(@.a ==]").unwrap_err();
+        assert!(matches!(err, ExpressionError::InvalidJsonPath(..)));
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_root() {
+        let err = parse_expression("response.body").unwrap_err();
+        assert!(matches!(err, ExpressionError::UnknownRoot(_)));
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_function() {
+        let err = parse_expression("body.items | count").unwrap_err();
+        assert!(matches!(err, ExpressionError::UnknownFunction(ref f) if f == "count"));
+    }
+
+    #[test]
+    fn test_parse_interpolation() {
+        let parsed =
+            parse_interpolation(r#" id ?? other_id ?? "1" | default: "a|b" | upper "#).unwrap();
+        assert_eq!(
+            parsed.sources,
+            vec![
+                InterpolationSource::Variable("id".to_string()),
+                InterpolationSource::Variable("other_id".to_string()),
+                InterpolationSource::Literal("1".to_string()),
+            ]
+        );
+        let filters: Vec<_> = parsed
+            .filters
+            .iter()
+            .map(|f| (f.name.as_str(), f.args.clone()))
+            .collect();
+        assert_eq!(
+            filters,
+            vec![("default", vec!["a|b".to_string()]), ("upper", vec![])]
+        );
+
+        let dynamic = parse_interpolation("$randomInt(1,100) | base64").unwrap();
+        assert_eq!(
+            dynamic.sources,
+            vec![InterpolationSource::Variable(
+                "$randomInt(1,100)".to_string()
+            )]
+        );
+
+        assert!(matches!(
+            parse_interpolation("name | shout"),
+            Err(ExpressionError::UnknownFunction(ref f)) if f == "shout"
+        ));
+        assert!(matches!(
+            parse_interpolation("name | default"),
+            Err(ExpressionError::ArgumentCount { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_pipe_arguments() {
+        let expr =
+            parse_expression(r#"res.body.items | filter(.active) | map(.user.id) | join(", ")"#)
+                .unwrap();
+        let Expression::Pipe {
+            input,
+            function,
+            args,
+        } = expr
+        else {
+            panic!("expected pipe");
+        };
+        assert_eq!(function, "join");
+        assert!(matches!(&args[..], [PipeArg::Literal(Literal::String(s))] if s == ", "));
+
+        let Expression::Pipe { function, args, .. } = *input else {
+            panic!("expected pipe");
+        };
+        assert_eq!(function, "map");
+        assert!(matches!(&args[..], [PipeArg::Field(segments)] if segments.len() == 2));
+
+        assert!(matches!(
+            parse_expression("body.items | split"),
+            Err(ExpressionError::ArgumentCount { ref function, expected: 1, found: 0 })
+                if function == "split"
+        ));
+        assert!(matches!(
+            parse_expression("body.items | reverse(1)"),
+            Err(ExpressionError::ArgumentCount { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_condition() {
+        assert!(matches!(
+            parse_condition(r#"{{role}} != "admin""#).unwrap(),
+            Condition::Compare {
+                left: Operand::Variable(ref name),
+                op: BinaryOperator::NotEquals,
+                right: Operand::Literal(Literal::String(ref value)),
+            } if name == "role" && value == "admin"
+        ));
+        assert!(matches!(
+            parse_condition("env == 'prod'").unwrap(),
+            Condition::Compare {
+                left: Operand::Environment,
+                op: BinaryOperator::Equals,
+                ..
+            }
+        ));
+        assert!(matches!(
+            parse_condition("{{feature_enabled}}").unwrap(),
+            Condition::Truthy(Operand::Variable(_))
+        ));
+        assert!(parse_condition("env == ").is_err());
+        assert!(parse_condition("env == 'a' 'b'").is_err());
+    }
+
+    #[test]
+    fn test_parse_until() {
+        assert!(matches!(
+            parse_until("body.status = done").unwrap(),
+            Expression::BinaryOp {
+                op: BinaryOperator::Equals,
+                ref right,
+                ..
+            } if matches!(**right, Expression::Literal(Literal::String(ref s)) if s == "done")
+        ));
+        assert!(matches!(
+            parse_until("body.items | length >= 3").unwrap(),
+            Expression::BinaryOp {
+                op: BinaryOperator::GreaterOrEqual,
+                ..
+            }
+        ));
+        assert!(matches!(
+            parse_until("body.count>=3").unwrap(),
+            Expression::BinaryOp {
+                op: BinaryOperator::GreaterOrEqual,
+                ref right,
+                ..
+            } if matches!(**right, Expression::Literal(Literal::Integer(3)))
+        ));
+        assert!(parse_until("body.jobs[?(@.state == 'failed')].id != null").is_ok());
+        assert!(matches!(
+            parse_until("body.message contains finished").unwrap(),
+            Expression::BinaryOp {
+                op: BinaryOperator::Contains,
+                ..
+            }
+        ));
+        assert!(parse_until("body.status").is_err());
+        assert!(parse_until("= done").is_err());
+
+        assert_eq!(parse_duration("500ms").unwrap(), 500);
+        assert_eq!(parse_duration("1.5s").unwrap(), 1500);
+        assert!(parse_duration("soon").is_err());
+    }
+}
@@ -10,7 +10,8 @@ use logos::Logos;
 #[logos(skip r"[ \t\n\f]+")]
 pub enum Token {
     // Literals
-    #[regex(r#""([^"\\]|\\.)*""#, |lex| lex.slice().to_string())]
+    #[regex(r#""([^"\\]|\\.)*""#, |lex| unquote(lex.slice()))]
+    #[regex(r#"'([^'\\]|\\.)*'"#, |lex| unquote(lex.slice()))]
     String(String),
 
     #[regex(r"-?[0-9]+", |lex| lex.slice().parse::<i64>().ok())]
@@ -23,7 +24,7 @@ pub enum Token {
     Boolean(bool),
 
     // Identifiers
-    #[regex(r"[a-zA-Z_][a-zA-Z0-9_]*", |lex| lex.slice().to_string())]
+    #[regex(r"[a-zA-Z_][a-zA-Z0-9_\-]*", |lex| lex.slice().to_string())]
     Identifier(String),
 
     // Operators
@@ -64,6 +65,24 @@ pub enum Token {
     #[token("|")]
     Pipe,
 
+    #[token("(")]
+    LeftParen,
+
+    #[token(")")]
+    RightParen,
+
+    #[token(",")]
+    Comma,
+
+    #[token("*")]
+    Star,
+
+    #[token("!")]
+    Bang,
+
+    #[token("$")]
+    Dollar,
+
     // Keywords
     #[token("exists")]
     Exists,
@@ -105,8 +124,83 @@ pub enum Token {
     Status,
 }
 
-pub fn tokenize(input: &str) -> Vec<Token> {
-    Token::lexer(input).filter_map(|t| t.ok()).collect()
+impl Token {
+    /// Text of a keyword or identifier token, used where keywords double as
+    /// property names (e.g. `body.status`)
+    pub fn as_name(&self) -> Option<&str> {
+        match self {
+            Self::Identifier(name) => Some(name),
+            Self::Exists => Some("exists"),
+            Self::IsArray => Some("is_array"),
+            Self::IsNumber => Some("is_number"),
+            Self::IsString => Some("is_string"),
+            Self::IsUuid => Some("is_uuid"),
+            Self::IsIso8601 => Some("is_iso8601"),
+            Self::Contains => Some("contains"),
+            Self::Matches => Some("matches"),
+            Self::Length => Some("length"),
+            Self::Res => Some("res"),
+            Self::Body => Some("body"),
+            Self::Headers => Some("headers"),
+            Self::Status => Some("status"),
+            Self::Boolean(true) => Some("true"),
+            Self::Boolean(false) => Some("false"),
+            _ => None,
+        }
+    }
+}
+
+/// Tokenize an expression, failing on the first unrecognised character
+pub fn tokenize(input: &str) -> Result<Vec<Token>, LexError> {
+    let mut lexer = Token::lexer(input);
+    let mut tokens = Vec::new();
+
+    while let Some(token) = lexer.next() {
+        match token {
+            Ok(token) => tokens.push(token),
+            Err(()) => {
+                return Err(LexError {
+                    position: lexer.span().start,
+                    found: lexer.slice().to_string(),
+                })
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("unexpected '{found}' at position {position}")]
+pub struct LexError {
+    pub position: usize,
+    pub found: String,
+}
+
+fn unquote(slice: &str) -> String {
+    let inner = &slice[1..slice.len() - 1];
+    let mut result = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') => result.push('\n'),
+                Some('t') => result.push('\t'),
+                Some(c @ ('"' | '\'' | '\\')) => result.push(c),
+                // Keep other escapes, such as `\d` in a regex
+                Some(other) => {
+                    result.push('\\');
+                    result.push(other);
+                }
+                None => {}
+            }
+        } else {
+            result.push(c);
+        }
+    }
+
+    result
 }
 
 #[cfg(test)]
@@ -115,7 +209,7 @@ mod tests {
 
     #[test]
     fn test_tokenize_simple() {
-        let tokens = tokenize("status = 200");
+        let tokens = tokenize("status = 200").unwrap();
         assert!(tokens.contains(&Token::Status));
         assert!(tokens.contains(&Token::Equals));
         assert!(tokens.contains(&Token::Integer(200)));
@@ -123,7 +217,7 @@ mod tests {
 
     #[test]
     fn test_tokenize_jsonpath() {
-        let tokens = tokenize("body.data[0].id");
+        let tokens = tokenize("body.data[0].id").unwrap();
         assert!(tokens.contains(&Token::Body));
         assert!(tokens.contains(&Token::Dot));
         assert!(tokens.contains(&Token::LeftBracket));
@@ -133,9 +227,28 @@ mod tests {
 
     #[test]
     fn test_tokenize_variable() {
-        let tokens = tokenize("{{user_id}}");
+        let tokens = tokenize("{{user_id}}").unwrap();
         assert!(tokens.contains(&Token::VariableStart));
         assert!(tokens.contains(&Token::Identifier("user_id".to_string())));
         assert!(tokens.contains(&Token::VariableEnd));
     }
+
+    #[test]
+    fn test_tokenize_header_name() {
+        let tokens = tokenize("headers.content-type").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Headers,
+                Token::Dot,
+                Token::Identifier("content-type".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_rejects_unknown_character() {
+        let err = tokenize("body.id # comment").unwrap_err();
+        assert_eq!(err.position, 8);
+    }
 }
@@ -4,14 +4,24 @@
 
 //! Parser module for .reqx files
 
+pub mod ast;
+mod expression;
 mod lexer;
-mod ast;
+
+pub use expression::{
+    is_snapshot_check, parse_assertion, parse_computed, parse_condition, parse_duration,
+    parse_expression, parse_interpolation, parse_until,
+};
+
+use expression::schema_reference;
 
 use anyhow::{Context, Result};
+use ast::{Computed, Condition, Expression, PathExpr, PathRoot, SNAPSHOT_FUNCTION};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
 
 /// Parsed .reqx file structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,7 +31,74 @@ pub struct ReqxFile {
     pub query: HashMap<String, String>,
     pub body: Option<BodySection>,
     pub assertions: Vec<Assertion>,
+    /// Variables computed, in order, before interpolation
+    pub pre_request: Vec<PreRequestVariable>,
     pub post_response: Vec<PostResponseScript>,
+    pub snapshot: Option<SnapshotSection>,
+    /// Script run after interpolation, before the request is sent
+    pub pre_request_script: Option<Script>,
+    /// Script run once the response has arrived
+    pub post_response_script: Option<Script>,
+    pub meta: MetaSection,
+    /// Rows to run the request with, one iteration each
+    pub data: Option<DataSection>,
+    /// Re-send the request until a condition holds
+    pub retry: Option<RetrySection>,
+}
+
+/// `[meta]` settings that control whether and how the request runs
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MetaSection {
+    /// Skip the request when this holds
+    pub skip_if: Option<MetaCondition>,
+    /// Skip the request unless this holds
+    pub run_if: Option<MetaCondition>,
+    /// Files that must run first, relative to the .reqx file
+    #[serde(default)]
+    pub depends_on: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetaCondition {
+    pub expression: String,
+    pub parsed: Condition,
+}
+
+/// `[retry]` settings for polling an endpoint until its response is ready
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetrySection {
+    pub until: String,
+    pub parsed: Expression,
+    /// Attempts in total, including the first
+    pub max_attempts: u32,
+    /// Wait before the second attempt
+    pub interval: Duration,
+    /// Factor the wait grows by after each attempt; 1 keeps it fixed
+    pub backoff: f64,
+}
+
+const DEFAULT_MAX_ATTEMPTS: u32 = 10;
+const DEFAULT_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+/// Longest wait `backoff` can grow to, unless `interval` itself is longer
+const MAX_RETRY_INTERVAL: Duration = Duration::from_secs(300);
+
+impl RetrySection {
+    /// The wait after `interval`, grown by `backoff` and capped so it
+    /// cannot overflow
+    pub fn next_interval(&self, interval: Duration) -> Duration {
+        let max = MAX_RETRY_INTERVAL.max(self.interval);
+        Duration::try_from_secs_f64(interval.as_secs_f64() * self.backoff)
+            .map_or(max, |next| next.min(max))
+    }
+}
+
+/// `[data]` file whose rows each run the request once
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DataSection {
+    /// CSV or JSON file, relative to the .reqx file
+    pub file: PathBuf,
+    /// Column that names each iteration, instead of its row number
+    pub name: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -41,13 +118,46 @@ pub enum BodySection {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Assertion {
     pub expression: String,
-    pub expected: String,
+    pub expected: serde_json::Value,
+    pub parsed: Expression,
+}
+
+/// Rhai script from `script = "..."` or `script_file = "..."` in
+/// `[pre-request]` or `[post-response]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Script {
+    /// The script file, or the .reqx file for inline scripts
+    pub origin: PathBuf,
+    pub source: String,
+}
+
+/// Keys of `[pre-request]` and `[post-response]` that hold a script rather
+/// than a variable
+const SCRIPT_KEYS: &[&str] = &["script", "script_file"];
+
+/// Snapshot settings, from a `[snapshot]` section or a `matches_snapshot`
+/// assertion
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotSection {
+    /// Where snapshots are stored: `<name>.snap.json` next to the .reqx file
+    pub file: PathBuf,
+    /// Body paths replaced by a placeholder before comparing, such as ids
+    /// and timestamps
+    pub ignore: Vec<PathExpr>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreRequestVariable {
+    pub variable: String,
+    pub expression: String,
+    pub parsed: Computed,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PostResponseScript {
     pub variable: String,
     pub expression: String,
+    pub parsed: Expression,
 }
 
 /// Parse a .reqx file from path
@@ -58,6 +168,217 @@ pub fn parse_file(path: &Path) -> Result<ReqxFile> {
     parse_content(&content, path)
 }
 
+/// Keys of the `[assert]` section, in order. TOML reads an unquoted dotted
+/// key such as `body.id` as a nested table, so each dotted key is split
+/// back into the path it was written as.
+fn assertion_keys(content: &str) -> Result<Vec<Vec<String>>> {
+    let document: toml_edit::DocumentMut = content.parse()?;
+    let mut keys = Vec::new();
+    if let Some(table) = document.get("assert").and_then(|item| item.as_table_like()) {
+        collect_dotted_keys(table, &mut Vec::new(), &mut keys);
+    }
+    Ok(keys)
+}
+
+fn collect_dotted_keys(
+    table: &dyn toml_edit::TableLike,
+    prefix: &mut Vec<String>,
+    keys: &mut Vec<Vec<String>>,
+) {
+    for (key, item) in table.iter() {
+        prefix.push(key.to_string());
+        match item.as_table_like() {
+            Some(nested) if nested.is_dotted() => collect_dotted_keys(nested, prefix, keys),
+            _ => keys.push(prefix.clone()),
+        }
+        prefix.pop();
+    }
+}
+
+/// Make the file named by a `{ schema = "..." }` assertion relative to the
+/// .reqx file rather than the working directory
+fn resolve_schema_path(expected: &mut serde_json::Value, path: &Path) {
+    let Some(schema) = schema_reference(expected) else {
+        return;
+    };
+    let resolved = match path.parent() {
+        Some(dir) => dir.join(schema).to_string_lossy().into_owned(),
+        None => return,
+    };
+    expected["schema"] = serde_json::Value::String(resolved);
+}
+
+/// Read the script of a `[pre-request]` or `[post-response]` section, with
+/// `script_file` relative to the .reqx file. Syntax errors are reported here
+/// rather than when the request runs.
+fn parse_script(table: &toml::Table, section: &str, path: &Path) -> Result<Option<Script>> {
+    let Some(section_table) = table.get(section).and_then(|v| v.as_table()) else {
+        return Ok(None);
+    };
+
+    let script = match (
+        section_table.get("script").and_then(|v| v.as_str()),
+        section_table.get("script_file").and_then(|v| v.as_str()),
+    ) {
+        (Some(_), Some(_)) => {
+            anyhow::bail!("[{}] has both 'script' and 'script_file'", section)
+        }
+        (Some(source), None) => Script {
+            origin: path.to_path_buf(),
+            source: source.to_string(),
+        },
+        (None, Some(file)) => {
+            let origin = path.parent().unwrap_or(Path::new("")).join(file);
+            let source = fs::read_to_string(&origin).with_context(|| {
+                format!("Failed to read script_file '{}' in [{}]", origin.display(), section)
+            })?;
+            Script { origin, source }
+        }
+        (None, None) => return Ok(None),
+    };
+
+    rhai::Engine::new_raw()
+        .compile(&script.source)
+        .map_err(|e| anyhow::anyhow!("{}", e))
+        .with_context(|| format!("Invalid script in [{}] ({})", section, script.origin.display()))?;
+
+    Ok(Some(script))
+}
+
+/// Parse a `[meta]` condition such as `skip_if = "env == 'prod'"`
+fn parse_meta_condition(meta: Option<&toml::Table>, key: &str) -> Result<Option<MetaCondition>> {
+    let Some(value) = meta.and_then(|t| t.get(key)) else {
+        return Ok(None);
+    };
+    let expression = value
+        .as_str()
+        .with_context(|| format!("'{}' in [meta] must be a string", key))?
+        .to_string();
+    let parsed = parse_condition(&expression)
+        .with_context(|| format!("Invalid condition for '{}' in [meta]", key))?;
+
+    Ok(Some(MetaCondition { expression, parsed }))
+}
+
+/// Parse `[meta] depends_on`, a list of .reqx files relative to this one
+fn parse_depends_on(meta: Option<&toml::Table>, path: &Path) -> Result<Vec<PathBuf>> {
+    let Some(value) = meta.and_then(|t| t.get("depends_on")) else {
+        return Ok(Vec::new());
+    };
+    let dir = path.parent().unwrap_or(Path::new(""));
+
+    value
+        .as_array()
+        .context("'depends_on' in [meta] must be a list of files")?
+        .iter()
+        .map(|v| {
+            let file = v
+                .as_str()
+                .context("'depends_on' in [meta] must be a list of files")?;
+            Ok(normalize_path(&dir.join(file)))
+        })
+        .collect()
+}
+
+/// Remove `.` and `..` components, so `users/../auth/login.reqx` is
+/// reported as `auth/login.reqx`
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(normalized.components().next_back(), Some(Component::Normal(_))) =>
+            {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Parse a `[retry]` section. `interval` is a duration such as `"500ms"` or
+/// a number of milliseconds.
+fn parse_retry(table: &toml::Table) -> Result<Option<RetrySection>> {
+    let Some(retry_table) = table.get("retry").and_then(|v| v.as_table()) else {
+        return Ok(None);
+    };
+
+    let until = retry_table
+        .get("until")
+        .and_then(|v| v.as_str())
+        .context("Missing 'until' in [retry]")?
+        .to_string();
+    let parsed = parse_until(&until).with_context(|| format!("Invalid 'until' in [retry]: {}", until))?;
+
+    let max_attempts = match retry_table.get("max_attempts") {
+        Some(v) => v
+            .as_integer()
+            .and_then(|n| u32::try_from(n).ok())
+            .filter(|n| *n > 0)
+            .context("'max_attempts' in [retry] must be a positive integer")?,
+        None => DEFAULT_MAX_ATTEMPTS,
+    };
+
+    let interval = match retry_table.get("interval") {
+        Some(toml::Value::Integer(ms)) if *ms >= 0 => Duration::from_millis(*ms as u64),
+        Some(toml::Value::String(s)) => Duration::from_millis(
+            parse_duration(s).with_context(|| format!("Invalid 'interval' in [retry]: {}", s))?,
+        ),
+        Some(_) => anyhow::bail!("'interval' in [retry] must be a duration such as \"2s\""),
+        None => DEFAULT_RETRY_INTERVAL,
+    };
+
+    let backoff = match retry_table.get("backoff") {
+        Some(v) => v
+            .as_float()
+            .or_else(|| v.as_integer().map(|n| n as f64))
+            .filter(|f| f.is_finite() && *f >= 1.0)
+            .context("'backoff' in [retry] must be a finite number of at least 1")?,
+        None => 1.0,
+    };
+
+    Ok(Some(RetrySection {
+        until,
+        parsed,
+        max_attempts,
+        interval,
+        backoff,
+    }))
+}
+
+/// Parse a `[data]` section, with `file` relative to the .reqx file
+fn parse_data(table: &toml::Table, path: &Path) -> Result<Option<DataSection>> {
+    let Some(data_table) = table.get("data").and_then(|v| v.as_table()) else {
+        return Ok(None);
+    };
+
+    let file = data_table
+        .get("file")
+        .and_then(|v| v.as_str())
+        .context("Missing 'file' in [data]")?;
+    let name = data_table
+        .get("name")
+        .map(|v| v.as_str().context("'name' in [data] must be a string"))
+        .transpose()?
+        .map(String::from);
+
+    Ok(Some(DataSection {
+        file: path.parent().unwrap_or(Path::new("")).join(file),
+        name,
+    }))
+}
+
+/// Parse a `[snapshot] ignore` entry, which must point into the body
+fn parse_ignore_path(path: &str) -> Result<PathExpr> {
+    match parse_expression(path) {
+        Ok(Expression::Path(expr)) if expr.root == PathRoot::Body => Ok(expr),
+        Ok(_) => anyhow::bail!("Invalid ignore path '{}' in [snapshot]: expected a body path", path),
+        Err(e) => Err(e).with_context(|| format!("Invalid ignore path '{}' in [snapshot]", path)),
+    }
+}
+
 /// Parse .reqx content
 pub fn parse_content(content: &str, path: &Path) -> Result<ReqxFile> {
     // Parse as TOML
@@ -121,17 +442,50 @@ pub fn parse_content(content: &str, path: &Path) -> Result<ReqxFile> {
     });
 
     // Parse [assert] section
-    let assertions = table
-        .get("assert")
+    let mut assertions = match table.get("assert").filter(|v| v.is_table()) {
+        Some(assert_table) => assertion_keys(content)
+            .with_context(|| format!("Failed to parse TOML in {}", path.display()))?
+            .into_iter()
+            .filter_map(|key| {
+                let value = key.iter().try_fold(assert_table, |v, segment| v.get(segment))?;
+                Some((key.join("."), value))
+            })
+            .map(|(k, v)| {
+                let mut expected = serde_json::to_value(v).unwrap_or_default();
+                resolve_schema_path(&mut expected, path);
+                let parsed = parse_assertion(&k, &expected)
+                    .with_context(|| format!("Invalid assertion '{}' in [assert]", k))?;
+                Ok(Assertion {
+                    expression: k,
+                    expected,
+                    parsed,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?,
+        None => Vec::new(),
+    };
+
+    // Parse [pre-request] section
+    let pre_request = table
+        .get("pre-request")
         .and_then(|v| v.as_table())
         .map(|t| {
             t.iter()
-                .map(|(k, v)| Assertion {
-                    expression: k.clone(),
-                    expected: v.as_str().unwrap_or(&v.to_string()).to_string(),
+                .filter(|(k, _)| !SCRIPT_KEYS.contains(&k.as_str()))
+                .map(|(k, v)| {
+                    let expression = v.as_str().unwrap_or(&v.to_string()).to_string();
+                    let parsed = parse_computed(&expression).with_context(|| {
+                        format!("Invalid value for '{}' in [pre-request]", k)
+                    })?;
+                    Ok(PreRequestVariable {
+                        variable: k.clone(),
+                        expression,
+                        parsed,
+                    })
                 })
-                .collect()
+                .collect::<Result<Vec<_>>>()
         })
+        .transpose()?
         .unwrap_or_default();
 
     // Parse [post-response] section
@@ -140,21 +494,84 @@ pub fn parse_content(content: &str, path: &Path) -> Result<ReqxFile> {
         .and_then(|v| v.as_table())
         .map(|t| {
             t.iter()
-                .map(|(k, v)| PostResponseScript {
-                    variable: k.clone(),
-                    expression: v.as_str().unwrap_or(&v.to_string()).to_string(),
+                .filter(|(k, _)| !SCRIPT_KEYS.contains(&k.as_str()))
+                .map(|(k, v)| {
+                    let expression = v.as_str().unwrap_or(&v.to_string()).to_string();
+                    let parsed = parse_expression(&expression).with_context(|| {
+                        format!("Invalid expression for '{}' in [post-response]", k)
+                    })?;
+                    Ok(PostResponseScript {
+                        variable: k.clone(),
+                        expression,
+                        parsed,
+                    })
                 })
-                .collect()
+                .collect::<Result<Vec<_>>>()
         })
+        .transpose()?
         .unwrap_or_default();
 
+    // Parse [snapshot] section, which snapshots the whole body unless an
+    // assertion already names what to snapshot
+    let snapshot_table = table.get("snapshot").and_then(|v| v.as_table());
+    if snapshot_table.is_some() && !assertions.iter().any(|a| is_snapshot_check(&a.parsed)) {
+        let expected = serde_json::Value::String(SNAPSHOT_FUNCTION.to_string());
+        assertions.push(Assertion {
+            expression: "body".to_string(),
+            parsed: parse_assertion("body", &expected)?,
+            expected,
+        });
+    }
+
+    let snapshot = if assertions.iter().any(|a| is_snapshot_check(&a.parsed)) {
+        let ignore = snapshot_table
+            .and_then(|t| t.get("ignore"))
+            .and_then(|v| v.as_array())
+            .map(|paths| {
+                paths
+                    .iter()
+                    .map(|p| parse_ignore_path(p.as_str().unwrap_or_default()))
+                    .collect::<Result<Vec<_>>>()
+            })
+            .transpose()?
+            .unwrap_or_default();
+
+        Some(SnapshotSection {
+            file: path.with_extension("snap.json"),
+            ignore,
+        })
+    } else {
+        None
+    };
+
+    let pre_request_script = parse_script(table, "pre-request", path)?;
+    let post_response_script = parse_script(table, "post-response", path)?;
+
+    // Parse [meta] section
+    let meta_table = table.get("meta").and_then(|v| v.as_table());
+    let meta = MetaSection {
+        skip_if: parse_meta_condition(meta_table, "skip_if")?,
+        run_if: parse_meta_condition(meta_table, "run_if")?,
+        depends_on: parse_depends_on(meta_table, path)?,
+    };
+
+    let data = parse_data(table, path)?;
+    let retry = parse_retry(table)?;
+
     Ok(ReqxFile {
         request: RequestSection { method, url },
         headers,
         query,
         body,
         assertions,
+        pre_request,
         post_response,
+        snapshot,
+        pre_request_script,
+        post_response_script,
+        meta,
+        data,
+        retry,
     })
 }
 
@@ -201,4 +618,244 @@ status = "201"
         assert_eq!(result.request.method, "POST");
         assert!(result.body.is_some());
     }
+
+    #[test]
+    fn test_parse_rejects_unknown_assertion() {
+        let content = r#"
+[request]
+method = "GET"
+url = "https://api.example.com/users"
+
+[assert]
+"response.code" = "200"
+"#;
+
+        let err = parse_content(content, Path::new("test.reqx")).unwrap_err();
+        assert!(err.to_string().contains("response.code"));
+    }
+
+    #[test]
+    fn test_parse_dotted_assertion_keys() {
+        let content = r#"
+[request]
+method = "POST"
+url = "https://api.example.com/users"
+
+[assert]
+status = 201
+body.id = "exists"
+body.name = "{{user_name}}"
+body.tags = { first = "a" }
+"headers.content-type" = "contains json"
+"#;
+
+        let reqx_file = parse_content(content, Path::new("test.reqx")).unwrap();
+        let keys: Vec<_> = reqx_file
+            .assertions
+            .iter()
+            .map(|a| a.expression.as_str())
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                "status",
+                "body.id",
+                "body.name",
+                "body.tags",
+                "headers.content-type"
+            ]
+        );
+        assert_eq!(reqx_file.assertions[3].expected, serde_json::json!({ "first": "a" }));
+    }
+
+    #[test]
+    fn test_parse_schema_path_relative_to_file() {
+        let content = r#"
+[request]
+method = "GET"
+url = "https://api.example.com/users"
+
+[assert]
+body = { schema = "schemas/user.json" }
+"#;
+
+        let reqx_file = parse_content(content, Path::new("api/users.reqx")).unwrap();
+        let expected = &reqx_file.assertions[0].expected;
+        assert_eq!(
+            Path::new(expected["schema"].as_str().unwrap()),
+            Path::new("api/schemas/user.json")
+        );
+    }
+
+    #[test]
+    fn test_parse_snapshot_section() {
+        let content = r#"
+[request]
+method = "GET"
+url = "https://api.example.com/users"
+
+[snapshot]
+ignore = ["body.data[*].id", "body..created_at"]
+"#;
+
+        let reqx_file = parse_content(content, Path::new("api/users.reqx")).unwrap();
+        let snapshot = reqx_file.snapshot.unwrap();
+        assert_eq!(snapshot.file, Path::new("api/users.snap.json"));
+        assert_eq!(snapshot.ignore.len(), 2);
+        assert_eq!(reqx_file.assertions.len(), 1);
+        assert!(is_snapshot_check(&reqx_file.assertions[0].parsed));
+
+        let invalid = content.replace("body..created_at", "status");
+        assert!(parse_content(&invalid, Path::new("users.reqx")).is_err());
+    }
+
+    #[test]
+    fn test_parse_scripts() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("sign.rhai"), "req.headers.sig = sha256(req.url);").unwrap();
+
+        let content = r#"
+[request]
+method = "GET"
+url = "https://api.example.com/users"
+
+[pre-request]
+script_file = "sign.rhai"
+
+[post-response]
+user_id = "res.body.id"
+script = "vars.seen = true;"
+"#;
+
+        let reqx_file = parse_content(content, &dir.path().join("users.reqx")).unwrap();
+        assert_eq!(reqx_file.post_response.len(), 1);
+        assert!(reqx_file.pre_request_script.unwrap().source.contains("sha256"));
+        assert!(reqx_file.post_response_script.is_some());
+
+        let broken = content.replace("vars.seen = true;", "if {");
+        assert!(parse_content(&broken, &dir.path().join("users.reqx")).is_err());
+    }
+
+    #[test]
+    fn test_parse_pre_request_in_order() {
+        let content = r#"
+[request]
+method = "GET"
+url = "https://api.example.com/users?nonce={{nonce}}"
+
+[pre-request]
+nonce = "{{$uuid}}"
+sig = "hmac_sha256({{secret}}, base64('{{nonce}}'))"
+auth = "Bearer {{sig}}"
+"#;
+
+        let reqx_file = parse_content(content, Path::new("test.reqx")).unwrap();
+        let names: Vec<_> = reqx_file.pre_request.iter().map(|v| v.variable.as_str()).collect();
+        assert_eq!(names, vec!["nonce", "sig", "auth"]);
+        assert!(matches!(
+            reqx_file.pre_request[1].parsed,
+            Computed::Call { ref function, ref args } if function == "hmac_sha256" && args.len() == 2
+        ));
+
+        let unknown = content.replace("hmac_sha256(", "hmac_md5(");
+        assert!(parse_content(&unknown, Path::new("test.reqx")).is_err());
+    }
+
+    #[test]
+    fn test_parse_meta_conditions() {
+        let content = r#"
+[meta]
+skip_if = "env == 'prod'"
+run_if = "{{role}} == 'admin'"
+depends_on = ["../auth/login.reqx"]
+
+[request]
+method = "DELETE"
+url = "https://api.example.com/users/1"
+"#;
+
+        let reqx_file = parse_content(content, Path::new("users/test.reqx")).unwrap();
+        assert_eq!(
+            reqx_file.meta.depends_on,
+            vec![PathBuf::from("auth/login.reqx")]
+        );
+        assert_eq!(
+            reqx_file.meta.skip_if.as_ref().map(|c| c.expression.as_str()),
+            Some("env == 'prod'")
+        );
+        assert!(reqx_file.meta.run_if.is_some());
+
+        let invalid = content.replace("env == 'prod'", "env ==");
+        assert!(parse_content(&invalid, Path::new("test.reqx")).is_err());
+    }
+
+    #[test]
+    fn test_parse_data_section() {
+        let content = r#"
+[request]
+method = "POST"
+url = "https://api.example.com/users"
+
+[data]
+file = "fixtures/users.csv"
+name = "email"
+"#;
+
+        let reqx_file = parse_content(content, Path::new("tests/create.reqx")).unwrap();
+        let data = reqx_file.data.unwrap();
+        assert_eq!(data.file, Path::new("tests/fixtures/users.csv"));
+        assert_eq!(data.name.as_deref(), Some("email"));
+
+        let missing = content.replace("file = \"fixtures/users.csv\"", "");
+        assert!(parse_content(&missing, Path::new("test.reqx")).is_err());
+    }
+
+    #[test]
+    fn test_parse_retry_section() {
+        let content = r#"
+[request]
+method = "GET"
+url = "https://api.example.com/jobs/1"
+
+[retry]
+until = "body.status = done"
+max_attempts = 5
+interval = "500ms"
+backoff = 2
+"#;
+
+        let retry = parse_content(content, Path::new("test.reqx")).unwrap().retry.unwrap();
+        assert_eq!(retry.until, "body.status = done");
+        assert_eq!(retry.max_attempts, 5);
+        assert_eq!(retry.interval, Duration::from_millis(500));
+        assert_eq!(retry.backoff, 2.0);
+
+        let invalid = content.replace("max_attempts = 5", "max_attempts = 0");
+        assert!(parse_content(&invalid, Path::new("test.reqx")).is_err());
+        let invalid = content.replace("backoff = 2", "backoff = inf");
+        assert!(parse_content(&invalid, Path::new("test.reqx")).is_err());
+    }
+
+    #[test]
+    fn test_retry_interval_is_capped() {
+        let content = r#"
+[request]
+method = "GET"
+url = "https://api.example.com/jobs/1"
+
+[retry]
+until = "body.status = done"
+max_attempts = 30
+interval = "1s"
+backoff = 10
+"#;
+
+        let retry = parse_content(content, Path::new("test.reqx")).unwrap().retry.unwrap();
+        let mut interval = retry.interval;
+        for _ in 1..retry.max_attempts {
+            interval = retry.next_interval(interval);
+        }
+        assert_eq!(retry.next_interval(Duration::from_secs(1)), Duration::from_secs(10));
+        assert_eq!(interval, MAX_RETRY_INTERVAL);
+    }
 }
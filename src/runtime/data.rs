@@ -0,0 +1,126 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Data files for data-driven runs, where a request runs once per row
+//!
+//! CSV files use their header row as column names and every cell is a
+//! string. JSON files hold an array of objects whose values keep their types.
+
+use super::json_value_to_string;
+use anyhow::{bail, Context, Result};
+use serde_json::{Map, Value};
+use std::path::Path;
+
+/// One row of a data file
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataRow {
+    /// Names the iteration in reports: the `name` column, or the row number
+    pub label: String,
+    /// Columns, set as variables for the iteration
+    pub values: Map<String, Value>,
+}
+
+/// Read every row of a `.csv` or `.json` file, labelling each by the `name`
+/// column when one is given
+pub fn load_data(file: &Path, name: Option<&str>) -> Result<Vec<DataRow>> {
+    let content = std::fs::read_to_string(file)
+        .with_context(|| format!("Failed to read data file: {}", file.display()))?;
+
+    let rows = match file.extension().and_then(|e| e.to_str()) {
+        Some("csv") => parse_csv(&content),
+        Some("json") => parse_json(&content),
+        _ => bail!(
+            "Unsupported data file: {} (expected .csv or .json)",
+            file.display()
+        ),
+    }
+    .with_context(|| format!("Invalid data file: {}", file.display()))?;
+
+    rows.into_iter()
+        .enumerate()
+        .map(|(i, values)| {
+            let label = match name {
+                Some(column) => match values.get(column) {
+                    Some(value) => json_value_to_string(value),
+                    None => bail!(
+                        "Row {} of {} has no column '{}'",
+                        i + 1,
+                        file.display(),
+                        column
+                    ),
+                },
+                None => format!("row {}", i + 1),
+            };
+            Ok(DataRow { label, values })
+        })
+        .collect()
+}
+
+fn parse_csv(content: &str) -> Result<Vec<Map<String, Value>>> {
+    let mut reader = csv::Reader::from_reader(content.as_bytes());
+    let headers = reader.headers()?.clone();
+
+    reader
+        .records()
+        .map(|record| {
+            let record = record?;
+            Ok(headers
+                .iter()
+                .zip(record.iter())
+                .map(|(column, cell)| (column.to_string(), Value::String(cell.to_string())))
+                .collect())
+        })
+        .collect()
+}
+
+fn parse_json(content: &str) -> Result<Vec<Map<String, Value>>> {
+    match serde_json::from_str(content)? {
+        Value::Array(rows) => rows
+            .into_iter()
+            .enumerate()
+            .map(|(i, row)| match row {
+                Value::Object(values) => Ok(values),
+                _ => bail!("row {} is not an object", i + 1),
+            })
+            .collect(),
+        _ => bail!("expected an array of objects"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_load_csv_and_json_rows() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let csv = dir.path().join("users.csv");
+        std::fs::write(
+            &csv,
+            "email,age\nalice@example.com,30\n\"bob, jr@example.com\",41\n",
+        )
+        .unwrap();
+        let rows = load_data(&csv, None).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].label, "row 2");
+        assert_eq!(rows[1].values["email"], "bob, jr@example.com");
+        assert_eq!(rows[0].values["age"], "30");
+
+        let json = dir.path().join("cases.json");
+        std::fs::write(
+            &json,
+            r#"[{ "case": "minimum", "qty": 1 }, { "case": "zero", "qty": 0 }]"#,
+        )
+        .unwrap();
+        let rows = load_data(&json, Some("case")).unwrap();
+        assert_eq!(rows[0].label, "minimum");
+        assert_eq!(rows[1].values["qty"], json!(0));
+
+        assert!(load_data(&json, Some("missing")).is_err());
+        std::fs::write(&json, r#"{ "case": "minimum" }"#).unwrap();
+        assert!(load_data(&json, None).is_err());
+    }
+}
@@ -0,0 +1,147 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Run order from `[meta] depends_on`
+
+use crate::parser::{parse_file, ReqxFile};
+use anyhow::{bail, Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Identifies a file however its path was written, so `auth/login.reqx` and
+/// `users/../auth/login.reqx` are the same dependency
+pub fn dependency_key(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Order files so each runs after everything it depends on, keeping the
+/// given order otherwise. Dependencies that were not selected, such as
+/// files outside `--filter`, are loaded and run too.
+pub fn order_by_dependencies(files: Vec<(PathBuf, ReqxFile)>) -> Result<Vec<(PathBuf, ReqxFile)>> {
+    let keys: Vec<PathBuf> = files.iter().map(|(path, _)| dependency_key(path)).collect();
+    let mut graph = Graph {
+        files: keys.iter().cloned().zip(files).collect(),
+        done: HashSet::new(),
+        stack: Vec::new(),
+        ordered: Vec::new(),
+    };
+
+    for key in &keys {
+        graph.visit(key)?;
+    }
+    Ok(graph.ordered)
+}
+
+struct Graph {
+    /// Files not yet ordered, by key
+    files: HashMap<PathBuf, (PathBuf, ReqxFile)>,
+    done: HashSet<PathBuf>,
+    /// Files being visited, to detect cycles
+    stack: Vec<(PathBuf, PathBuf)>,
+    ordered: Vec<(PathBuf, ReqxFile)>,
+}
+
+impl Graph {
+    fn visit(&mut self, key: &PathBuf) -> Result<()> {
+        if self.done.contains(key) {
+            return Ok(());
+        }
+        let Some((path, reqx_file)) = self.files.remove(key) else {
+            return Ok(());
+        };
+
+        self.stack.push((key.clone(), path.clone()));
+        for dependency in &reqx_file.meta.depends_on {
+            let dependency_key = dependency_key(dependency);
+
+            if let Some(start) = self.stack.iter().position(|(k, _)| *k == dependency_key) {
+                let cycle: Vec<String> = self.stack[start..]
+                    .iter()
+                    .map(|(_, p)| p.display().to_string())
+                    .chain(std::iter::once(dependency.display().to_string()))
+                    .collect();
+                bail!("Dependency cycle: {}", cycle.join(" -> "));
+            }
+
+            if !self.done.contains(&dependency_key) && !self.files.contains_key(&dependency_key) {
+                let loaded = parse_file(dependency).with_context(|| {
+                    format!(
+                        "Failed to load dependency '{}' of {}",
+                        dependency.display(),
+                        path.display()
+                    )
+                })?;
+                self.files
+                    .insert(dependency_key.clone(), (dependency.clone(), loaded));
+            }
+            self.visit(&dependency_key)?;
+        }
+        self.stack.pop();
+
+        self.done.insert(key.clone());
+        self.ordered.push((path, reqx_file));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, name: &str, depends_on: &[&str]) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let content = format!(
+            "[meta]\ndepends_on = {:?}\n\n[request]\nmethod = \"GET\"\nurl = \"http://localhost/{}\"\n",
+            depends_on, name
+        );
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn names(ordered: &[(PathBuf, ReqxFile)]) -> Vec<String> {
+        ordered
+            .iter()
+            .map(|(path, _)| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_dependencies_run_first() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "auth/login.reqx", &[]);
+        let orders = write(dir.path(), "shop/a_orders.reqx", &["b_cart.reqx"]);
+        let cart = write(dir.path(), "shop/b_cart.reqx", &["../auth/login.reqx"]);
+
+        // login.reqx is outside the selection, so it is loaded
+        let files = [orders, cart]
+            .into_iter()
+            .map(|path| {
+                let reqx_file = parse_file(&path).unwrap();
+                (path, reqx_file)
+            })
+            .collect();
+
+        let ordered = order_by_dependencies(files).unwrap();
+        assert_eq!(
+            names(&ordered),
+            vec!["login.reqx", "b_cart.reqx", "a_orders.reqx"]
+        );
+    }
+
+    #[test]
+    fn test_dependency_cycle_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = write(dir.path(), "a.reqx", &["b.reqx"]);
+        write(dir.path(), "b.reqx", &["a.reqx"]);
+
+        let files = vec![(a.clone(), parse_file(&a).unwrap())];
+        let err = order_by_dependencies(files).unwrap_err();
+        assert!(err.to_string().starts_with("Dependency cycle:"), "{}", err);
+
+        let missing = write(dir.path(), "c.reqx", &["missing.reqx"]);
+        let files = vec![(missing.clone(), parse_file(&missing).unwrap())];
+        assert!(order_by_dependencies(files).is_err());
+    }
+}
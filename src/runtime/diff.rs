@@ -0,0 +1,138 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Structural diffs between expected and actual JSON values
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// One difference between two JSON values
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiffEntry {
+    /// JSON pointer to the differing value, relative to the compared one
+    pub pointer: String,
+    pub kind: DiffKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actual: Option<Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffKind {
+    /// Present in the actual value only
+    Added,
+    /// Present in the expected value only
+    Removed,
+    /// Present in both with different values
+    Changed,
+}
+
+/// Diff two JSON values key by key and index by index
+pub fn diff(expected: &Value, actual: &Value) -> Vec<DiffEntry> {
+    let mut entries = Vec::new();
+    diff_at(String::new(), expected, actual, &mut entries);
+    entries
+}
+
+fn diff_at(pointer: String, expected: &Value, actual: &Value, entries: &mut Vec<DiffEntry>) {
+    match (expected, actual) {
+        (Value::Object(expected), Value::Object(actual)) => {
+            for (key, expected) in expected {
+                let pointer = format!("{}/{}", pointer, escape_pointer(key));
+                match actual.get(key) {
+                    Some(actual) => diff_at(pointer, expected, actual, entries),
+                    None => entries.push(removed(pointer, expected)),
+                }
+            }
+            for (key, actual) in actual {
+                if !expected.contains_key(key) {
+                    entries.push(added(
+                        format!("{}/{}", pointer, escape_pointer(key)),
+                        actual,
+                    ));
+                }
+            }
+        }
+        (Value::Array(expected), Value::Array(actual)) => {
+            for (i, expected) in expected.iter().enumerate() {
+                let pointer = format!("{}/{}", pointer, i);
+                match actual.get(i) {
+                    Some(actual) => diff_at(pointer, expected, actual, entries),
+                    None => entries.push(removed(pointer, expected)),
+                }
+            }
+            for (i, actual) in actual.iter().enumerate().skip(expected.len()) {
+                entries.push(added(format!("{}/{}", pointer, i), actual));
+            }
+        }
+        (Value::Number(e), Value::Number(a)) if e.as_f64() == a.as_f64() => {}
+        _ if expected == actual => {}
+        _ => entries.push(DiffEntry {
+            pointer,
+            kind: DiffKind::Changed,
+            expected: Some(expected.clone()),
+            actual: Some(actual.clone()),
+        }),
+    }
+}
+
+fn added(pointer: String, actual: &Value) -> DiffEntry {
+    DiffEntry {
+        pointer,
+        kind: DiffKind::Added,
+        expected: None,
+        actual: Some(actual.clone()),
+    }
+}
+
+fn removed(pointer: String, expected: &Value) -> DiffEntry {
+    DiffEntry {
+        pointer,
+        kind: DiffKind::Removed,
+        expected: Some(expected.clone()),
+        actual: None,
+    }
+}
+
+/// Escape a key for use in a JSON pointer (RFC 6901)
+fn escape_pointer(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_diff_objects_and_arrays() {
+        let expected = json!({ "name": "Alice", "roles": ["admin", "dev"], "a/b": 1, "age": 30 });
+        let actual = json!({ "name": "Bob", "roles": ["admin"], "a/b": 1.0, "email": "b@x.io" });
+
+        let entries = diff(&expected, &actual);
+        let summary: Vec<_> = entries
+            .iter()
+            .map(|e| (e.kind, e.pointer.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (DiffKind::Removed, "/age"),
+                (DiffKind::Changed, "/name"),
+                (DiffKind::Removed, "/roles/1"),
+                (DiffKind::Added, "/email"),
+            ]
+        );
+        assert_eq!(entries[1].actual, Some(json!("Bob")));
+    }
+
+    #[test]
+    fn test_diff_equal_values() {
+        let value = json!({ "data": [{ "id": 1 }] });
+        assert!(diff(&value, &value).is_empty());
+        assert_eq!(diff(&json!("a"), &json!(1))[0].pointer, "");
+    }
+}
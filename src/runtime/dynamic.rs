@@ -0,0 +1,241 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Dynamic variables such as `{{$uuid}}` and `{{$randomInt(1,100)}}`
+
+use super::functions;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use rand::distributions::Alphanumeric;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::Mutex;
+
+const FIRST_NAMES: &[&str] = &[
+    "Alice", "Bob", "Carol", "David", "Emma", "Farid", "Grace", "Hiro", "Ines", "Jonas", "Kofi",
+    "Lena", "Mateo", "Nadia", "Oscar", "Priya", "Quinn", "Rosa", "Sven", "Tara",
+];
+
+const LAST_NAMES: &[&str] = &[
+    "Anderson", "Brown", "Chen", "Diaz", "Evans", "Fischer", "Garcia", "Haddad", "Ito", "Jensen",
+    "Kowalski", "Lopez", "Martin", "Nguyen", "Okafor", "Patel", "Rossi", "Silva", "Tanaka",
+    "Weber",
+];
+
+/// Generates dynamic variable values from a shared random number generator
+/// and clock. Runs with the same seed and frozen clock produce the same
+/// values.
+pub struct DynamicVariables {
+    rng: Mutex<StdRng>,
+    seed: u64,
+    now: Option<DateTime<Utc>>,
+}
+
+impl DynamicVariables {
+    /// Seed the generator, picking a random seed when none is given so it
+    /// can still be reported and replayed
+    pub fn new(seed: Option<u64>, now: Option<DateTime<Utc>>) -> Self {
+        let seed = seed.unwrap_or_else(rand::random);
+        Self {
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+            seed,
+            now,
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The current time, or the frozen time from `--now`
+    pub fn now(&self) -> DateTime<Utc> {
+        self.now.unwrap_or_else(Utc::now)
+    }
+
+    /// Resolve a dynamic variable, written without its `$`. Returns `None`
+    /// for names that are not dynamic variables.
+    pub fn resolve(&self, name: &str) -> Result<Option<String>> {
+        let now = self.now();
+        let (name, args) = split_call(name);
+        let arg = |i: usize| args.get(i).map(|a| a.trim()).filter(|a| !a.is_empty());
+
+        let value = match name {
+            "uuid" => uuid::Builder::from_random_bytes(self.with_rng(|rng| rng.gen()))
+                .into_uuid()
+                .to_string(),
+            "ulid" => {
+                let random = self.with_rng(|rng| rng.gen::<u128>());
+                ulid::Ulid::from_parts(now.timestamp_millis() as u64, random).to_string()
+            }
+            "timestamp" => now.timestamp().to_string(),
+            "unix_ms" => now.timestamp_millis().to_string(),
+            "date" => shift(now, arg(0))?.format("%Y-%m-%d").to_string(),
+            "datetime" => {
+                let time = shift(now, arg(0))?;
+                match arg(1) {
+                    Some(format) => time.format(format).to_string(),
+                    None => time.to_rfc3339(),
+                }
+            }
+            "random" => self.with_rng(|rng| rng.gen_range(0..1_000_000)).to_string(),
+            "randomInt" => {
+                let min = parse_arg(arg(0), 0, "randomInt")?;
+                let max = parse_arg(arg(1), 1000, "randomInt")?;
+                if min > max {
+                    bail!("$randomInt: min {} is greater than max {}", min, max);
+                }
+                self.with_rng(|rng| rng.gen_range(min..=max)).to_string()
+            }
+            "randomString" => {
+                let length = parse_arg(arg(0), 16, "randomString")?;
+                self.with_rng(|rng| {
+                    rng.sample_iter(&Alphanumeric)
+                        .take(length)
+                        .map(char::from)
+                        .collect()
+                })
+            }
+            "randomName" => {
+                let (first, last) = self.random_name();
+                format!("{} {}", first, last)
+            }
+            "randomEmail" => {
+                let (first, last) = self.random_name();
+                let n = self.with_rng(|rng| rng.gen_range(1..10_000));
+                format!("{}.{}{}@example.com", first, last, n).to_lowercase()
+            }
+            "base64" => functions::base64_encode(&args.join(",")),
+            "sha256" => functions::sha256(&args.join(",")),
+            _ => return Ok(None),
+        };
+
+        Ok(Some(value))
+    }
+
+    fn random_name(&self) -> (&'static str, &'static str) {
+        self.with_rng(|rng| {
+            (
+                FIRST_NAMES[rng.gen_range(0..FIRST_NAMES.len())],
+                LAST_NAMES[rng.gen_range(0..LAST_NAMES.len())],
+            )
+        })
+    }
+
+    fn with_rng<T>(&self, f: impl FnOnce(&mut StdRng) -> T) -> T {
+        let mut rng = self.rng.lock().unwrap_or_else(|e| e.into_inner());
+        f(&mut rng)
+    }
+}
+
+/// Split `randomInt(1,100)` into its name and arguments
+fn split_call(name: &str) -> (&str, Vec<&str>) {
+    match name.split_once('(') {
+        Some((function, rest)) => {
+            let inner = rest.strip_suffix(')').unwrap_or(rest);
+            let args = if inner.trim().is_empty() {
+                Vec::new()
+            } else {
+                inner.split(',').collect()
+            };
+            (function.trim(), args)
+        }
+        None => (name.trim(), Vec::new()),
+    }
+}
+
+fn parse_arg<T: std::str::FromStr>(arg: Option<&str>, default: T, function: &str) -> Result<T> {
+    match arg {
+        Some(arg) => arg
+            .parse()
+            .map_err(|_| anyhow::anyhow!("${}: invalid argument '{}'", function, arg)),
+        None => Ok(default),
+    }
+}
+
+/// Apply an offset such as `+1d`, `-2h`, `+30m`, `+10s` or `+1w`
+fn shift(now: DateTime<Utc>, offset: Option<&str>) -> Result<DateTime<Utc>> {
+    let Some(offset) = offset else {
+        return Ok(now);
+    };
+
+    let split = offset.char_indices().last().map_or(0, |(i, _)| i);
+    let (amount, unit) = offset.split_at(split);
+    let amount: i64 = amount
+        .trim_start_matches('+')
+        .parse()
+        .with_context(|| format!("invalid date offset '{}', expected e.g. +1d or -2h", offset))?;
+
+    let duration = match unit {
+        "s" => Duration::seconds(amount),
+        "m" => Duration::minutes(amount),
+        "h" => Duration::hours(amount),
+        "d" => Duration::days(amount),
+        "w" => Duration::weeks(amount),
+        _ => bail!(
+            "invalid date offset '{}', expected a unit of s, m, h, d or w",
+            offset
+        ),
+    };
+
+    Ok(now + duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn resolve(name: &str) -> String {
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        DynamicVariables::new(None, Some(now))
+            .resolve(name)
+            .unwrap()
+            .unwrap_or_else(|| panic!("{} is not a dynamic variable", name))
+    }
+
+    #[test]
+    fn test_resolve_generators() {
+        let n: i64 = resolve("randomInt(1,100)").parse().unwrap();
+        assert!((1..=100).contains(&n));
+        assert_eq!(resolve("randomString(12)").len(), 12);
+        assert!(resolve("randomEmail").ends_with("@example.com"));
+        assert_eq!(resolve("randomName").split(' ').count(), 2);
+        assert_eq!(resolve("ulid").len(), 26);
+        assert!(uuid::Uuid::parse_str(&resolve("uuid")).is_ok());
+        assert_eq!(resolve("unix_ms"), "1709294400000");
+    }
+
+    #[test]
+    fn test_resolve_dates_and_encodings() {
+        assert_eq!(resolve("datetime(+1d, %Y-%m-%d)"), "2024-03-02");
+        assert_eq!(resolve("datetime(-2h)"), "2024-03-01T10:00:00+00:00");
+        assert_eq!(resolve("date(+1w)"), "2024-03-08");
+        assert_eq!(resolve("base64(user:pass)"), "dXNlcjpwYXNz");
+        assert_eq!(resolve("sha256(abc)").len(), 64);
+
+        let variables = DynamicVariables::new(None, None);
+        assert!(variables.resolve("HOME").unwrap().is_none());
+        assert!(variables.resolve("randomInt(9,1)").is_err());
+        assert!(variables.resolve("datetime(+1y)").is_err());
+    }
+
+    #[test]
+    fn test_seed_makes_values_reproducible() {
+        let sequence = |seed| {
+            let variables = DynamicVariables::new(Some(seed), None);
+            [
+                "uuid",
+                "randomInt(1,1000000)",
+                "randomString",
+                "randomEmail",
+                "ulid",
+            ]
+            .map(|name| variables.resolve(name).unwrap().unwrap())
+        };
+
+        let first = sequence(42);
+        assert_eq!(sequence(42)[..4], first[..4]);
+        assert_ne!(sequence(7)[0], first[0]);
+    }
+}
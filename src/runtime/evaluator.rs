@@ -0,0 +1,893 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Evaluation of parsed expressions against a response
+
+use super::{is_email, is_iso8601, is_uuid, json_value_to_string, pipe};
+use crate::http::Response;
+use crate::parser::ast::{
+    BinaryOperator, Expression, Literal, PathExpr, PathRoot, PathSegment, Quantifier,
+    ValidationFunction, SCHEMA_FUNCTION,
+};
+use jsonpath_rust::{JsonPathFinder, JsonPathInst, JsonPathValue};
+use regex::Regex;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+pub struct Evaluator<'a> {
+    response: &'a Response,
+    variables: &'a HashMap<String, Value>,
+    loose: bool,
+    schemas: Option<&'a SchemaCache>,
+}
+
+/// JSON Schemas compiled so far, by file path, so a schema is read and
+/// compiled once per run rather than once per check and element
+#[derive(Default)]
+pub struct SchemaCache(Mutex<HashMap<String, Result<jsonschema::Validator, String>>>);
+
+impl<'a> Evaluator<'a> {
+    pub fn new(response: &'a Response, variables: &'a HashMap<String, Value>) -> Self {
+        Self {
+            response,
+            variables,
+            loose: false,
+            schemas: None,
+        }
+    }
+
+    /// Reuse schemas compiled by earlier checks
+    pub fn schemas(mut self, schemas: &'a SchemaCache) -> Self {
+        self.schemas = Some(schemas);
+        self
+    }
+
+    /// Compare values by their string form instead of by JSON type
+    pub fn loose(mut self, loose: bool) -> Self {
+        self.loose = loose;
+        self
+    }
+
+    /// Evaluate an expression to a JSON value. `None` means the value does not exist.
+    pub fn evaluate(&self, expression: &Expression) -> Option<Value> {
+        match expression {
+            Expression::Literal(literal) => Some(literal_to_json(literal)),
+            Expression::Variable(name) => self.variables.get(name).cloned(),
+            Expression::Path(path) => self.resolve_path(path),
+            Expression::Pipe {
+                input,
+                function,
+                args,
+            } => pipe::apply(function, args, self.evaluate(input)?),
+            Expression::BinaryOp { .. }
+            | Expression::FunctionCall { .. }
+            | Expression::ForEach { .. } => Some(Value::Bool(self.check(expression).passed)),
+        }
+    }
+
+    /// Evaluate an assertion check
+    pub fn check(&self, expression: &Expression) -> Check {
+        match expression {
+            Expression::BinaryOp { left, op, right } => {
+                let actual = self.evaluate(left);
+                let expected = self.evaluate(right);
+                let passed = match (&actual, &expected) {
+                    (Some(actual), Some(expected)) => compare(actual, *op, expected, self.loose),
+                    _ => false,
+                };
+                Check::new(passed, actual)
+            }
+            Expression::FunctionCall { name, args } if name == SCHEMA_FUNCTION => {
+                let actual = args.first().and_then(|arg| self.evaluate(arg));
+                let schema = args.get(1).and_then(|arg| self.evaluate(arg));
+                let violations = match (&actual, &schema) {
+                    (Some(value), Some(Value::String(schema))) => {
+                        self.validate_schema(schema, value)
+                    }
+                    _ => Vec::new(),
+                };
+                Check {
+                    passed: actual.is_some() && violations.is_empty(),
+                    actual,
+                    failures: Vec::new(),
+                    violations,
+                }
+            }
+            Expression::FunctionCall { name, args } => {
+                let actual = args.first().and_then(|arg| self.evaluate(arg));
+                let passed = ValidationFunction::from_str(name)
+                    .map(|f| validate(f, actual.as_ref()))
+                    .unwrap_or(false);
+                Check::new(passed, actual)
+            }
+            Expression::ForEach { quantifier, check } => self.check_each(*quantifier, check),
+            other => {
+                let actual = self.evaluate(other);
+                let passed = matches!(actual, Some(Value::Bool(true)));
+                Check::new(passed, actual)
+            }
+        }
+    }
+
+    /// Run a check once per value matched by its subject path
+    fn check_each(&self, quantifier: Quantifier, check: &Expression) -> Check {
+        let elements = check
+            .subject_path()
+            .map(|path| self.expand_path(path))
+            .unwrap_or_default();
+
+        let mut passed_count = 0;
+        let mut failures = Vec::new();
+        let mut violations = Vec::new();
+        let mut values = Vec::with_capacity(elements.len());
+
+        for (label, value) in elements {
+            let element = self.check(&bind_subject(check, &value));
+            let counts_as_failure = match quantifier {
+                Quantifier::NoneOf => element.passed,
+                _ => !element.passed,
+            };
+            if element.passed {
+                passed_count += 1;
+            }
+            if counts_as_failure && quantifier == Quantifier::All {
+                // Locate schema errors within the matched values
+                let index = values.len();
+                violations.extend(element.violations.into_iter().map(|violation| {
+                    SchemaViolation {
+                        pointer: format!("/{}{}", index, violation.pointer),
+                        message: violation.message,
+                    }
+                }));
+            }
+            if counts_as_failure {
+                failures.push(ElementFailure {
+                    path: label,
+                    actual: element.actual,
+                });
+            }
+            values.push(value);
+        }
+
+        let passed = match quantifier {
+            Quantifier::All => !values.is_empty() && failures.is_empty(),
+            Quantifier::Any => passed_count > 0,
+            Quantifier::NoneOf => passed_count == 0,
+        };
+
+        Check {
+            passed,
+            actual: (!values.is_empty()).then_some(Value::Array(values)),
+            // Every element fails an unmet `any`; listing them adds nothing
+            failures: if quantifier == Quantifier::Any {
+                Vec::new()
+            } else {
+                failures
+            },
+            violations,
+        }
+    }
+
+    /// Validate a value against a JSON Schema file
+    fn validate_schema(&self, path: &str, value: &Value) -> Vec<SchemaViolation> {
+        match self.schemas {
+            Some(SchemaCache(schemas)) => {
+                let mut schemas = schemas.lock().unwrap_or_else(|e| e.into_inner());
+                let validator = schemas
+                    .entry(path.to_string())
+                    .or_insert_with(|| compile_schema(path));
+                schema_violations(path, validator, value)
+            }
+            None => schema_violations(path, &compile_schema(path), value),
+        }
+    }
+
+    fn resolve_path(&self, path: &PathExpr) -> Option<Value> {
+        let mut matches = self.expand_path(path);
+
+        if path.is_multi() {
+            let values: Vec<Value> = matches.into_iter().map(|(_, value)| value).collect();
+            (!values.is_empty()).then_some(Value::Array(values))
+        } else if matches.is_empty() {
+            None
+        } else {
+            Some(matches.swap_remove(0).1)
+        }
+    }
+
+    /// Resolve a path to every value it matches, each labelled with its
+    /// concrete location (e.g. `data[3].id`)
+    fn expand_path(&self, path: &PathExpr) -> Vec<(String, Value)> {
+        let (label, root, segments) = match path.root {
+            PathRoot::Status => (
+                String::new(),
+                Value::from(self.response.status),
+                &path.segments[..],
+            ),
+            PathRoot::Body => (
+                String::new(),
+                self.response.body.clone(),
+                &path.segments[..],
+            ),
+            PathRoot::Duration => (
+                String::new(),
+                Value::from(self.response.duration.as_millis() as u64),
+                &path.segments[..],
+            ),
+            PathRoot::Size => (
+                String::new(),
+                Value::from(self.response.size),
+                &path.segments[..],
+            ),
+            PathRoot::Text => (
+                String::new(),
+                Value::String(self.response.text().to_string()),
+                &path.segments[..],
+            ),
+            PathRoot::Cookies => (
+                String::new(),
+                cookies(&self.response.headers.get_all("set-cookie")),
+                &path.segments[..],
+            ),
+            PathRoot::Location => match self.response.headers.get("location") {
+                Some(location) => (String::new(), parse_location(location), &path.segments[..]),
+                None => return Vec::new(),
+            },
+            PathRoot::Headers => match path.segments.split_first() {
+                Some((PathSegment::Property(name), rest)) => {
+                    let values = self.response.headers.get_all(name);
+                    if values.is_empty() {
+                        return Vec::new();
+                    }
+                    // Index into repeated headers (`set-cookie[1]`); otherwise
+                    // combine them as HTTP does
+                    let value = match rest.first() {
+                        Some(PathSegment::Index(_) | PathSegment::Wildcard) => {
+                            values.into_iter().map(Value::from).collect()
+                        }
+                        _ => header_value(name, &values.join(", ")),
+                    };
+                    (name.to_ascii_lowercase(), value, rest)
+                }
+                _ => {
+                    let headers = &self.response.headers;
+                    let object = headers
+                        .names()
+                        .into_iter()
+                        .map(|name| {
+                            let value = header_value(name, &headers.get_all(name).join(", "));
+                            (name.to_ascii_lowercase(), value)
+                        })
+                        .collect();
+                    (String::new(), Value::Object(object), &path.segments[..])
+                }
+            },
+        };
+
+        let mut current = vec![(label, root)];
+        for segment in segments {
+            let mut next = Vec::new();
+            for (label, mut value) in current {
+                match segment {
+                    PathSegment::Property(name) => {
+                        if let Some(v) = value.get_mut(name) {
+                            next.push((property_label(&label, name), v.take()));
+                        }
+                    }
+                    PathSegment::Index(idx) => {
+                        if let Some(v) = value.get_mut(idx) {
+                            next.push((format!("{}[{}]", label, idx), v.take()));
+                        }
+                    }
+                    PathSegment::Wildcard => {
+                        if let Value::Array(items) = value {
+                            for (i, item) in items.into_iter().enumerate() {
+                                next.push((format!("{}[{}]", label, i), item));
+                            }
+                        }
+                    }
+                    PathSegment::Variable(name) => {
+                        let Some(key) = self.variables.get(name).map(json_value_to_string) else {
+                            continue;
+                        };
+                        let found = match key.parse::<usize>() {
+                            Ok(idx) if value.is_array() => value.get_mut(idx),
+                            _ => value.get_mut(&key),
+                        };
+                        if let Some(v) = found {
+                            next.push((property_label(&label, &key), v.take()));
+                        }
+                    }
+                    PathSegment::Query { path, .. } => {
+                        for (location, v) in run_query(&value, path) {
+                            next.push((property_label(&label, &location), v));
+                        }
+                    }
+                }
+            }
+            current = next;
+        }
+
+        current
+    }
+}
+
+/// Outcome of an assertion check
+#[derive(Debug)]
+pub struct Check {
+    pub passed: bool,
+    /// Value of the check's subject; an array of all matches for wildcard paths
+    pub actual: Option<Value>,
+    /// Elements of a wildcard path that broke the check
+    pub failures: Vec<ElementFailure>,
+    /// Errors from a JSON Schema check
+    pub violations: Vec<SchemaViolation>,
+}
+
+impl Check {
+    fn new(passed: bool, actual: Option<Value>) -> Self {
+        Self {
+            passed,
+            actual,
+            failures: Vec::new(),
+            violations: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ElementFailure {
+    pub path: String,
+    pub actual: Option<Value>,
+}
+
+#[derive(Debug)]
+pub struct SchemaViolation {
+    /// JSON pointer to the offending value, relative to the checked value
+    pub pointer: String,
+    pub message: String,
+}
+
+/// Read and compile a JSON Schema file. The draft is taken from the
+/// schema's `$schema` keyword, defaulting to 2020-12.
+fn compile_schema(path: &str) -> Result<jsonschema::Validator, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let schema: Value = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+    jsonschema::validator_for(&schema).map_err(|e| e.to_string())
+}
+
+fn schema_violations(
+    path: &str,
+    validator: &Result<jsonschema::Validator, String>,
+    value: &Value,
+) -> Vec<SchemaViolation> {
+    match validator {
+        Ok(validator) => validator
+            .iter_errors(value)
+            .map(|error| SchemaViolation {
+                pointer: error.instance_path.to_string(),
+                message: error.to_string(),
+            })
+            .collect(),
+        Err(err) => vec![SchemaViolation {
+            pointer: String::new(),
+            message: format!("invalid schema {}: {}", path, err),
+        }],
+    }
+}
+
+/// A header's value, with Content-Length as the number of bytes it is
+fn header_value(name: &str, value: &str) -> Value {
+    match value.parse::<u64>() {
+        Ok(n) if name.eq_ignore_ascii_case("content-length") => Value::from(n),
+        _ => Value::String(value.to_string()),
+    }
+}
+
+/// Cookie values by name from `Set-Cookie` headers, ignoring attributes
+fn cookies(set_cookies: &[&str]) -> Value {
+    set_cookies
+        .iter()
+        .filter_map(|cookie| {
+            let pair = cookie.split(';').next()?;
+            let (name, value) = pair.split_once('=')?;
+            Some((name.trim().to_string(), Value::from(value.trim())))
+        })
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+/// Split a `Location` header into `url`, `path`, `segments` and `query`, so
+/// e.g. the id in `/orders/42` is `location.segments[1]`
+fn parse_location(location: &str) -> Value {
+    // Relative locations are resolved against a placeholder origin
+    let base = url::Url::parse("http://localhost/").expect("valid base URL");
+    let Ok(url) = base.join(location) else {
+        return serde_json::json!({ "url": location });
+    };
+
+    let segments: Vec<Value> = url
+        .path_segments()
+        .map(|segments| {
+            segments
+                .filter(|s| !s.is_empty())
+                .map(Value::from)
+                .collect()
+        })
+        .unwrap_or_default();
+    let query: serde_json::Map<_, _> = url
+        .query_pairs()
+        .map(|(k, v)| (k.into_owned(), Value::from(v.into_owned())))
+        .collect();
+
+    serde_json::json!({
+        "url": location,
+        "path": url.path(),
+        "segments": segments,
+        "query": query,
+    })
+}
+
+/// Replace a check's subject path with an already resolved value
+fn bind_subject(expression: &Expression, value: &Value) -> Expression {
+    match expression {
+        Expression::Path(_) => Expression::Literal(Literal::Json(value.clone())),
+        Expression::BinaryOp { left, op, right } => Expression::BinaryOp {
+            left: Box::new(bind_subject(left, value)),
+            op: *op,
+            right: right.clone(),
+        },
+        Expression::FunctionCall { name, args } => Expression::FunctionCall {
+            name: name.clone(),
+            args: args
+                .iter()
+                .enumerate()
+                .map(|(i, arg)| {
+                    if i == 0 {
+                        bind_subject(arg, value)
+                    } else {
+                        arg.clone()
+                    }
+                })
+                .collect(),
+        },
+        Expression::Pipe {
+            input,
+            function,
+            args,
+        } => Expression::Pipe {
+            input: Box::new(bind_subject(input, value)),
+            function: function.clone(),
+            args: args.clone(),
+        },
+        other => other.clone(),
+    }
+}
+
+fn property_label(label: &str, name: &str) -> String {
+    if label.is_empty() || name.starts_with('[') {
+        format!("{}{}", label, name.trim_start_matches('.'))
+    } else {
+        format!("{}.{}", label, name.trim_start_matches('.'))
+    }
+}
+
+/// Run a JSONPath query, returning each match with its location relative to
+/// the queried value (e.g. `.data[3].id`)
+fn run_query(value: &Value, path: &str) -> Vec<(String, Value)> {
+    let Ok(query) = path.parse::<JsonPathInst>() else {
+        return Vec::new();
+    };
+    let finder = JsonPathFinder::new(Box::new(value.clone()), Box::new(query));
+
+    finder
+        .find_slice()
+        .into_iter()
+        .filter_map(|m| match m {
+            JsonPathValue::Slice(v, location) => Some((
+                location
+                    .trim_start_matches('$')
+                    .replace(".['", ".")
+                    .replace("']", ""),
+                v.clone(),
+            )),
+            JsonPathValue::NewValue(v) => Some((String::new(), v)),
+            JsonPathValue::NoValue => None,
+        })
+        .collect()
+}
+
+pub(super) fn literal_to_json(literal: &Literal) -> Value {
+    match literal {
+        Literal::String(s) => Value::String(s.clone()),
+        Literal::Integer(n) => Value::from(*n),
+        Literal::Float(f) => Value::from(*f),
+        Literal::Boolean(b) => Value::Bool(*b),
+        Literal::Null => Value::Null,
+        Literal::Json(value) => value.clone(),
+    }
+}
+
+pub(super) fn compare(actual: &Value, op: BinaryOperator, expected: &Value, loose: bool) -> bool {
+    match op {
+        BinaryOperator::Equals => values_equal(actual, expected, loose),
+        BinaryOperator::NotEquals => !values_equal(actual, expected, loose),
+        BinaryOperator::Contains => match actual {
+            Value::Array(items) => items.iter().any(|item| values_equal(item, expected, loose)),
+            _ => json_value_to_string(actual).contains(&json_value_to_string(expected)),
+        },
+        BinaryOperator::Matches => Regex::new(&json_value_to_string(expected))
+            .map(|re| re.is_match(&json_value_to_string(actual)))
+            .unwrap_or(false),
+        BinaryOperator::LessThan
+        | BinaryOperator::GreaterThan
+        | BinaryOperator::LessOrEqual
+        | BinaryOperator::GreaterOrEqual => match (as_number(actual), as_number(expected)) {
+            (Some(a), Some(e)) => match op {
+                BinaryOperator::LessThan => a < e,
+                BinaryOperator::GreaterThan => a > e,
+                BinaryOperator::LessOrEqual => a <= e,
+                _ => a >= e,
+            },
+            _ => false,
+        },
+    }
+}
+
+/// Equality by JSON type, where `1` and `1.0` are equal but `1` and `"1"` are
+/// not. Loose mode compares string forms instead.
+fn values_equal(actual: &Value, expected: &Value, loose: bool) -> bool {
+    if loose {
+        return json_value_to_string(actual) == json_value_to_string(expected);
+    }
+
+    match (actual, expected) {
+        (Value::Number(a), Value::Number(b)) => match (a.as_i64(), b.as_i64()) {
+            (Some(a), Some(b)) => a == b,
+            _ => a.as_f64() == b.as_f64(),
+        },
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(x, y)| values_equal(x, y, false))
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(k, v)| b.get(k).is_some_and(|w| values_equal(v, w, false)))
+        }
+        _ => actual == expected,
+    }
+}
+
+/// Numeric view of a value; numeric strings (e.g. header values) count as numbers
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn validate(function: ValidationFunction, value: Option<&Value>) -> bool {
+    let Some(value) = value else {
+        return function == ValidationFunction::NotExists;
+    };
+
+    match function {
+        ValidationFunction::Exists => true,
+        ValidationFunction::NotExists => false,
+        ValidationFunction::IsArray => value.is_array(),
+        ValidationFunction::IsObject => value.is_object(),
+        ValidationFunction::IsNumber => value.is_number(),
+        ValidationFunction::IsString => value.is_string(),
+        ValidationFunction::IsUuid => is_uuid(&json_value_to_string(value)),
+        ValidationFunction::IsIso8601 => is_iso8601(&json_value_to_string(value)),
+        ValidationFunction::IsEmail => is_email(&json_value_to_string(value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_assertion, parse_expression};
+    use std::time::Duration;
+
+    fn response() -> Response {
+        Response {
+            headers: [
+                ("Content-Type", "application/json"),
+                ("Content-Length", "182"),
+                ("Set-Cookie", "sid=abc123; Path=/; HttpOnly"),
+                ("Set-Cookie", "theme=dark; Path=/"),
+            ]
+            .into_iter()
+            .collect(),
+            size: 182,
+            duration: Duration::from_millis(12),
+            ..Response::test_json(
+                200,
+                serde_json::json!({
+                    "data": [
+                        { "id": "7f9c2a4e-1b3d-4c5e-8f6a-9b0c1d2e3f4a", "email": "a@example.com" },
+                        { "id": "0d1e2f3a-4b5c-4d6e-8f7a-8b9c0d1e2f3a", "email": "b@example.com" }
+                    ],
+                    "total": 2
+                }),
+            )
+        }
+    }
+
+    #[test]
+    fn test_check_status_and_header() {
+        let response = response();
+        let variables = HashMap::new();
+        let evaluator = Evaluator::new(&response, &variables);
+
+        let status = parse_assertion("status", &Value::from(200)).unwrap();
+        assert!(evaluator.check(&status).passed);
+
+        let header =
+            parse_assertion("headers.content-type", &Value::from("application/json")).unwrap();
+        assert!(evaluator.check(&header).passed);
+    }
+
+    #[test]
+    fn test_check_validations() {
+        let response = response();
+        let variables = HashMap::new();
+        let evaluator = Evaluator::new(&response, &variables);
+
+        let uuid = parse_assertion("body.data[1].id", &Value::from("is_uuid")).unwrap();
+        assert!(evaluator.check(&uuid).passed);
+
+        let missing = parse_assertion("body.data[5]", &Value::from("!exists")).unwrap();
+        assert!(evaluator.check(&missing).passed);
+
+        let email = parse_assertion("body.data[0].email", &Value::from("is_email")).unwrap();
+        assert!(evaluator.check(&email).passed);
+    }
+
+    #[test]
+    fn test_check_operators() {
+        let response = response();
+        let variables = HashMap::new();
+        let evaluator = Evaluator::new(&response, &variables);
+
+        let cases = [
+            ("body.total", "> 1", true),
+            ("body.total", "<= 1", false),
+            ("body.total", "!= 3", true),
+            ("headers.content-type", "contains json", true),
+            ("body.data[0].email", "matches ^[a-z]+@", true),
+            ("body.data[0].email", "matches ^[0-9]+@", false),
+        ];
+
+        for (key, value, expected) in cases {
+            let check = parse_assertion(key, &Value::from(value)).unwrap();
+            assert_eq!(
+                evaluator.check(&check).passed,
+                expected,
+                "{} = {}",
+                key,
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn test_check_strict_and_loose_equality() {
+        let response = response();
+        let variables = HashMap::new();
+        let strict = Evaluator::new(&response, &variables);
+        let loose = Evaluator::new(&response, &variables).loose(true);
+
+        let number = parse_assertion("body.total", &Value::from(2)).unwrap();
+        assert!(strict.check(&number).passed);
+
+        let string = parse_assertion("body.total", &Value::from("2")).unwrap();
+        assert!(!strict.check(&string).passed);
+        assert!(loose.check(&string).passed);
+
+        let float = parse_assertion("body.total", &Value::from(2.0)).unwrap();
+        assert!(strict.check(&float).passed);
+
+        let status = parse_assertion("status", &Value::from("200")).unwrap();
+        assert!(!strict.check(&status).passed);
+        assert!(loose.check(&status).passed);
+    }
+
+    #[test]
+    fn test_evaluate_json_path() {
+        let response = response();
+        let variables = HashMap::new();
+        let evaluator = Evaluator::new(&response, &variables);
+
+        let last = parse_expression("body.data[-1].email").unwrap();
+        assert_eq!(
+            evaluator.evaluate(&last),
+            Some(Value::from("b@example.com"))
+        );
+
+        let filtered = parse_expression("$.data[?(@.email == 'a@example.com')].email").unwrap();
+        assert_eq!(
+            evaluator.evaluate(&filtered),
+            Some(serde_json::json!(["a@example.com"]))
+        );
+
+        let recursive = parse_expression("res.body..email | length").unwrap();
+        assert_eq!(evaluator.evaluate(&recursive), Some(Value::from(2)));
+
+        let slice = parse_expression("body.data[0:1]").unwrap();
+        assert_eq!(
+            evaluator
+                .evaluate(&slice)
+                .unwrap()
+                .as_array()
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let missing = parse_expression("body.data[?(@.email == 'z@example.com')]").unwrap();
+        assert_eq!(evaluator.evaluate(&missing), None);
+    }
+
+    #[test]
+    fn test_check_duration_and_size() {
+        let response = response();
+        let variables = HashMap::new();
+        let evaluator = Evaluator::new(&response, &variables);
+
+        for (key, expected, passed) in [
+            ("duration", "< 500ms", true),
+            ("duration", "< 2s", true),
+            ("duration", "> 0.01s", true),
+            ("res.duration", "< 10", false),
+            ("size", "< 1MB", true),
+            ("size", "= 182", true),
+            ("headers.content-length", "< 1KB", true),
+            ("headers.content-length", ">= 200", false),
+        ] {
+            let check = parse_assertion(key, &Value::from(expected)).unwrap();
+            assert_eq!(
+                evaluator.check(&check).passed,
+                passed,
+                "{} {}",
+                key,
+                expected
+            );
+        }
+
+        let content_length = parse_assertion("headers.content-length", &Value::from(182)).unwrap();
+        assert!(evaluator.check(&content_length).passed);
+    }
+
+    #[test]
+    fn test_check_headers() {
+        let response = response();
+        let variables = HashMap::new();
+        let evaluator = Evaluator::new(&response, &variables);
+
+        for (key, expected, passed) in [
+            ("headers.CONTENT-TYPE", "exists", true),
+            ("headers.x-request-id", "!exists", true),
+            ("headers.link", "exists", false),
+            (
+                "headers.content-type",
+                "matches ^application/(json|xml)$",
+                true,
+            ),
+            ("headers.set-cookie", "contains theme=dark", true),
+            ("headers.set-cookie[0]", "matches ^sid=", true),
+            ("headers.Set-Cookie[1]", "theme=dark; Path=/", true),
+            ("headers.set-cookie[2]", "!exists", true),
+            ("any headers.set-cookie[*]", "contains HttpOnly", true),
+        ] {
+            let check = parse_assertion(key, &Value::from(expected)).unwrap();
+            assert_eq!(
+                evaluator.check(&check).passed,
+                passed,
+                "{} {}",
+                key,
+                expected
+            );
+        }
+
+        let all =
+            parse_assertion("headers.set-cookie[*]", &Value::from("contains HttpOnly")).unwrap();
+        let check = evaluator.check(&all);
+        assert!(!check.passed);
+        assert_eq!(check.failures[0].path, "set-cookie[1]");
+    }
+
+    #[test]
+    fn test_check_wildcard_quantifiers() {
+        let mut response = response();
+        response.body["data"]
+            .as_array_mut()
+            .unwrap()
+            .push(serde_json::json!({ "id": 42, "email": "c@example.com" }));
+        let variables = HashMap::new();
+        let evaluator = Evaluator::new(&response, &variables);
+
+        let all = parse_assertion("body.data[*].id", &Value::from("is_uuid")).unwrap();
+        let check = evaluator.check(&all);
+        assert!(!check.passed);
+        assert_eq!(check.failures.len(), 1);
+        assert_eq!(check.failures[0].path, "data[2].id");
+        assert_eq!(check.failures[0].actual, Some(Value::from(42)));
+
+        let any = parse_assertion("any body.data[*].id", &Value::from(42)).unwrap();
+        assert!(evaluator.check(&any).passed);
+
+        let none = parse_assertion("none body.data[*].email", &Value::from("is_uuid")).unwrap();
+        assert!(evaluator.check(&none).passed);
+
+        let query = parse_assertion("body..id", &Value::from("is_uuid")).unwrap();
+        let check = evaluator.check(&query);
+        assert!(!check.passed);
+        assert_eq!(check.failures[0].path, "data[2].id");
+    }
+
+    #[test]
+    fn test_evaluate_pipe() {
+        let response = response();
+        let variables = HashMap::new();
+        let evaluator = Evaluator::new(&response, &variables);
+
+        let expr = parse_expression("res.body.data | length").unwrap();
+        assert_eq!(evaluator.evaluate(&expr), Some(Value::from(2)));
+    }
+
+    #[test]
+    fn test_evaluate_text_cookie_and_location_captures() {
+        let mut response = response();
+        response.raw = r#"<form><input name="csrf" value="tok-123"></form>"#.to_string();
+        response.body = Value::String(response.raw.clone());
+        response.headers = [
+            ("Set-Cookie", "sid=abc123; Path=/; HttpOnly"),
+            ("Location", "/orders/42?step=pay"),
+        ]
+        .into_iter()
+        .collect();
+        let variables = HashMap::new();
+        let evaluator = Evaluator::new(&response, &variables);
+        let evaluate = |input: &str| evaluator.evaluate(&parse_expression(input).unwrap());
+
+        assert_eq!(
+            evaluate(r#"res.text | regex('name="csrf" value="([^"]+)"')"#),
+            Some(Value::from("tok-123"))
+        );
+        assert_eq!(evaluate("res.cookies.sid"), Some(Value::from("abc123")));
+        assert_eq!(evaluate("res.cookies.theme"), None);
+        assert_eq!(
+            evaluate("res.location.segments[1]"),
+            Some(Value::from("42"))
+        );
+        assert_eq!(
+            evaluate("res.location.query.step"),
+            Some(Value::from("pay"))
+        );
+        assert_eq!(
+            evaluate("res.headers.location | regex('/orders/(\\d+)')"),
+            Some(Value::from("42"))
+        );
+        assert!(parse_expression("res.text | regex('(')").is_err());
+    }
+
+    #[test]
+    fn test_text_is_the_body_as_received() {
+        let mut response = response();
+        response.raw = "{\n  \"total\": 2,\n  \"data\": []\n}".to_string();
+        let variables = HashMap::new();
+        let evaluator = Evaluator::new(&response, &variables);
+
+        let contains = parse_assertion("res.text", &Value::from("contains \"total\": 2")).unwrap();
+        assert!(evaluator.check(&contains).passed);
+        assert_eq!(
+            evaluator.evaluate(&parse_expression(r#"res.text | regex('^\{\n  "(\w+)"')"#).unwrap()),
+            Some(Value::from("total"))
+        );
+    }
+}
@@ -0,0 +1,100 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! String functions shared by `[pre-request]` values and scripts
+
+use super::json_value_to_string;
+use anyhow::{bail, Result};
+use base64::Engine as _;
+use hmac::{Hmac, Mac};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+/// Call a function from `parser::ast::COMPUTE_FUNCTIONS`
+pub fn call(name: &str, args: &[String]) -> Result<String> {
+    Ok(match (name, args) {
+        ("sha256", [input]) => sha256(input),
+        ("hmac_sha256", [key, message]) => hmac_sha256(key, message),
+        ("base64", [input]) => base64_encode(input),
+        ("base64_decode", [input]) => base64_decode(input)?,
+        ("urlencode", [input]) => urlencode(input),
+        ("upper", [input]) => input.to_uppercase(),
+        ("lower", [input]) => input.to_lowercase(),
+        _ => bail!("cannot call {} with {} argument(s)", name, args.len()),
+    })
+}
+
+/// Apply a filter from `parser::ast::INTERPOLATION_FILTERS` that takes no
+/// arguments
+pub fn filter(name: &str, value: &Value) -> Result<String> {
+    let input = json_value_to_string(value);
+    Ok(match name {
+        "upper" => input.to_uppercase(),
+        "base64" => base64_encode(&input),
+        "urlencode" => urlencode(&input),
+        // Serialized, for embedding in raw JSON bodies
+        "json" => serde_json::to_string(value)?,
+        _ => bail!("unknown filter '{}'", name),
+    })
+}
+
+/// Hex-encoded SHA-256 digest
+pub fn sha256(input: &str) -> String {
+    hex(&Sha256::digest(input.as_bytes()))
+}
+
+/// Hex-encoded HMAC-SHA256 signature
+pub fn hmac_sha256(key: &str, message: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(message.as_bytes());
+    hex(&mac.finalize().into_bytes())
+}
+
+pub fn base64_encode(input: &str) -> String {
+    base64::engine::general_purpose::STANDARD.encode(input)
+}
+
+pub fn base64_decode(input: &str) -> Result<String> {
+    let bytes = base64::engine::general_purpose::STANDARD.decode(input.trim())?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+pub fn urlencode(input: &str) -> String {
+    url::form_urlencoded::byte_serialize(input.as_bytes()).collect()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_call_functions() {
+        let args = |values: &[&str]| values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            call("sha256", &args(&["abc"])).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            call(
+                "hmac_sha256",
+                &args(&["key", "The quick brown fox jumps over the lazy dog"])
+            )
+            .unwrap(),
+            "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+        assert_eq!(call("base64", &args(&["hello"])).unwrap(), "aGVsbG8=");
+        assert_eq!(
+            call("base64_decode", &args(&["aGVsbG8="])).unwrap(),
+            "hello"
+        );
+        assert_eq!(call("urlencode", &args(&["a b&c"])).unwrap(), "a+b%26c");
+        assert!(call("sha256", &args(&["a", "b"])).is_err());
+    }
+}
@@ -4,20 +4,60 @@
 
 //! Runtime module for executing requests and assertions
 
+mod data;
+mod dependencies;
+mod diff;
+mod dynamic;
+mod evaluator;
+mod functions;
+mod pipe;
+mod script;
+mod snapshot;
+mod state;
+
+pub use data::{load_data, DataRow};
+pub use dependencies::{dependency_key, order_by_dependencies};
+pub use diff::{DiffEntry, DiffKind};
+pub use evaluator::{Evaluator, SchemaCache};
+pub use state::{state_path, State};
+
 use crate::config::Config;
 use crate::http::Response;
-use crate::parser::ReqxFile;
-use anyhow::Result;
+use crate::parser::ast::{
+    BinaryOperator, Computed, Condition, Expression, InterpolationSource, Literal, Operand,
+    PathExpr, PathSegment, Quantifier, SCHEMA_FUNCTION,
+};
+use crate::parser::{
+    is_snapshot_check, parse_assertion, parse_duration, parse_interpolation, parse_until,
+    ReqxFile, SnapshotSection,
+};
+use dynamic::DynamicVariables;
+use snapshot::SnapshotOutcome;
+use anyhow::{Context, Result};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 use std::time::Duration;
 
 /// Execution context holding variables and configuration
 pub struct ExecutionContext {
     pub config: Config,
-    pub variables: HashMap<String, String>,
+    /// Variables keep their JSON type, so captured objects and numbers can
+    /// be sent on unchanged
+    pub variables: HashMap<String, Value>,
+    /// Overwrite stored snapshots instead of comparing against them
+    pub update_snapshots: bool,
+    /// Name of the selected environment, for `env` in `[meta]` conditions
+    pub environment: Option<String>,
+    /// Where `[state] persist` variables are saved, once loaded
+    state_file: Option<PathBuf>,
+    /// Label of the data row being run, which keeps snapshots apart per row
+    iteration: Option<String>,
+    schemas: SchemaCache,
+    dynamic: DynamicVariables,
 }
 
 impl ExecutionContext {
@@ -26,17 +66,79 @@ impl ExecutionContext {
 
         // Copy environment variables
         for (key, value) in &config.variables {
-            variables.insert(key.clone(), value.clone());
+            variables.insert(key.clone(), Value::String(value.clone()));
+        }
+
+        Self {
+            environment: config.environment.clone(),
+            config,
+            variables,
+            update_snapshots: false,
+            state_file: None,
+            iteration: None,
+            schemas: SchemaCache::default(),
+            dynamic: DynamicVariables::new(None, None),
+        }
+    }
+
+    pub fn set_variable(&mut self, key: String, value: impl Into<Value>) {
+        self.variables.insert(key, value.into());
+    }
+
+    /// Set the columns of a data row as variables, returning what they
+    /// replaced so `restore_variables` can undo it after the iteration
+    pub fn set_row(&mut self, row: &DataRow) -> Vec<(String, Option<Value>)> {
+        self.iteration = Some(row.label.clone());
+        row.values
+            .iter()
+            .map(|(key, value)| {
+                let previous = self.variables.insert(key.clone(), value.clone());
+                (key.clone(), previous)
+            })
+            .collect()
+    }
+
+    pub fn restore_variables(&mut self, saved: Vec<(String, Option<Value>)>) {
+        self.iteration = None;
+        for (key, previous) in saved {
+            match previous {
+                Some(value) => self.variables.insert(key, value),
+                None => self.variables.remove(&key),
+            };
+        }
+    }
+
+    /// Reload `[state] persist` variables saved by earlier runs from `path`
+    /// and save captured ones there from now on. Environment values take
+    /// precedence over saved ones, so an edited environment is not
+    /// overridden by a stale token; `--var` overrides both.
+    pub fn load_state(&mut self, path: PathBuf) -> Result<()> {
+        if self.config.state.persist.is_empty() {
+            return Ok(());
+        }
+
+        let state = State::load(&path)?;
+        for (name, value) in state.live(chrono::Utc::now()) {
+            if self.config.state.persist.contains(name) && !self.config.variables.contains_key(name) {
+                self.variables.insert(name.clone(), value);
+            }
         }
+        self.state_file = Some(path);
+        Ok(())
+    }
 
-        Self { config, variables }
+    /// Drive dynamic variables from a fixed seed and, optionally, a frozen
+    /// clock so a run can be replayed
+    pub fn set_generators(&mut self, seed: Option<u64>, now: Option<chrono::DateTime<chrono::Utc>>) {
+        self.dynamic = DynamicVariables::new(seed, now);
     }
 
-    pub fn set_variable(&mut self, key: String, value: String) {
-        self.variables.insert(key, value);
+    /// Seed of the random generator behind dynamic variables
+    pub fn seed(&self) -> u64 {
+        self.dynamic.seed()
     }
 
-    pub fn get_variable(&self, key: &str) -> Option<&String> {
+    pub fn get_variable(&self, key: &str) -> Option<&Value> {
         self.variables.get(key)
     }
 
@@ -45,64 +147,253 @@ impl ExecutionContext {
         let mut result = reqx_file.clone();
 
         // Interpolate URL
-        result.request.url = self.interpolate_string(&result.request.url)?;
+        result.request.url = self
+            .interpolate_string(&result.request.url)
+            .context("Failed to interpolate [request] url")?;
 
         // Interpolate headers
-        for value in result.headers.values_mut() {
-            *value = self.interpolate_string(value)?;
+        for (name, value) in result.headers.iter_mut() {
+            *value = self
+                .interpolate_string(value)
+                .with_context(|| format!("Failed to interpolate header '{}'", name))?;
         }
 
         // Interpolate query params
-        for value in result.query.values_mut() {
-            *value = self.interpolate_string(value)?;
+        for (name, value) in result.query.iter_mut() {
+            *value = self
+                .interpolate_string(value)
+                .with_context(|| format!("Failed to interpolate query parameter '{}'", name))?;
         }
 
         // Interpolate body (if JSON)
         if let Some(crate::parser::BodySection::Json(ref mut json)) = result.body {
-            *json = self.interpolate_json(json)?;
+            *json = self
+                .interpolate_json(json)
+                .context("Failed to interpolate [body]")?;
+        }
+
+        // Interpolate assertion expectations, re-parsing any that changed so
+        // operators and types apply to the substituted value
+        for assertion in &mut result.assertions {
+            let location = || format!("Failed to interpolate assertion '{}'", assertion.expression);
+            let expected = self
+                .interpolate_json(&assertion.expected)
+                .with_context(location)?;
+            if expected != assertion.expected {
+                assertion.parsed = parse_assertion(&assertion.expression, &expected)
+                    .with_context(|| {
+                        format!("Invalid assertion '{}' after interpolation", assertion.expression)
+                    })?;
+                assertion.expected = expected;
+            }
+            assertion.parsed = self
+                .interpolate_expression(&assertion.parsed)
+                .with_context(location)?;
+        }
+
+        // Interpolate the [retry] condition, re-parsing it if it changed
+        if let Some(retry) = &mut result.retry {
+            let location = format!("Failed to interpolate [retry] until '{}'", retry.until);
+            let until = self
+                .interpolate_string(&retry.until)
+                .context(location.clone())?;
+            if until != retry.until {
+                retry.parsed = parse_until(&until)
+                    .with_context(|| format!("Invalid [retry] until '{}' after interpolation", until))?;
+                retry.until = until;
+            }
+            retry.parsed = self
+                .interpolate_expression(&retry.parsed)
+                .context(location)?;
+        }
+
+        // Interpolate post-response expressions
+        for script in &mut result.post_response {
+            script.parsed = self
+                .interpolate_expression(&script.parsed)
+                .with_context(|| format!("Failed to interpolate [post-response] '{}'", script.variable))?;
         }
 
         Ok(result)
     }
 
+    /// Replace `{{name}}` references inside a parsed expression with their values
+    fn interpolate_expression(&self, expression: &Expression) -> Result<Expression> {
+        Ok(match expression {
+            Expression::Variable(name) => Expression::Literal(match self.resolve_value(name)? {
+                Value::String(s) => Literal::String(s),
+                other => Literal::Json(other),
+            }),
+            Expression::Path(path) => Expression::Path(PathExpr {
+                root: path.root,
+                segments: path
+                    .segments
+                    .iter()
+                    .map(|segment| match segment {
+                        PathSegment::Variable(name) => {
+                            let value = self.resolve_variable(name)?;
+                            Ok(match value.parse::<usize>() {
+                                Ok(idx) => PathSegment::Index(idx),
+                                Err(_) => PathSegment::Property(value),
+                            })
+                        }
+                        other => Ok(other.clone()),
+                    })
+                    .collect::<Result<_>>()?,
+            }),
+            Expression::BinaryOp { left, op, right } => Expression::BinaryOp {
+                left: Box::new(self.interpolate_expression(left)?),
+                op: *op,
+                right: Box::new(self.interpolate_expression(right)?),
+            },
+            Expression::FunctionCall { name, args } => Expression::FunctionCall {
+                name: name.clone(),
+                args: args
+                    .iter()
+                    .map(|a| self.interpolate_expression(a))
+                    .collect::<Result<_>>()?,
+            },
+            Expression::Pipe {
+                input,
+                function,
+                args,
+            } => Expression::Pipe {
+                input: Box::new(self.interpolate_expression(input)?),
+                function: function.clone(),
+                args: args.clone(),
+            },
+            Expression::ForEach { quantifier, check } => Expression::ForEach {
+                quantifier: *quantifier,
+                check: Box::new(self.interpolate_expression(check)?),
+            },
+            Expression::Literal(_) => expression.clone(),
+        })
+    }
+
     fn interpolate_string(&self, input: &str) -> Result<String> {
-        let re = Regex::new(r"\{\{([^}]+)\}\}")?;
-        let mut result = input.to_string();
-
-        for cap in re.captures_iter(input) {
-            let var_name = &cap[1];
-            let full_match = &cap[0];
-
-            let value = match var_name {
-                "$uuid" => uuid::Uuid::new_v4().to_string(),
-                "$timestamp" => chrono::Utc::now().timestamp().to_string(),
-                "$random" => rand_number().to_string(),
-                "$date" => chrono::Utc::now().format("%Y-%m-%d").to_string(),
-                "$datetime" => chrono::Utc::now().to_rfc3339(),
-                name => {
-                    // Check if it's an env var reference
-                    if name.starts_with('$') {
-                        std::env::var(&name[1..]).unwrap_or_default()
-                    } else {
-                        self.variables.get(name).cloned().unwrap_or_else(|| {
-                            // Try environment variable
-                            std::env::var(name).unwrap_or_default()
-                        })
-                    }
-                }
-            };
+        let mut result = String::with_capacity(input.len());
+        let mut rest = input;
 
-            result = result.replace(full_match, &value);
+        // Resolve each reference on its own, so repeated generators such as
+        // {{$uuid}} each get their own value
+        while let Some(range) = find_reference(rest) {
+            result.push_str(&rest[..range.start]);
+            let reference = &rest[range.start + 2..range.end - 2];
+            // Resolve references in arguments first, as in
+            // {{$base64({{user}}:{{pass}})}}
+            let value = if reference.contains("{{") {
+                self.resolve_variable(&self.interpolate_string(reference)?)?
+            } else {
+                self.resolve_variable(reference)?
+            };
+            result.push_str(&value);
+            rest = &rest[range.end..];
         }
 
+        result.push_str(rest);
         Ok(result)
     }
 
+    /// Resolve the contents of a `{{ }}` reference as text
+    fn resolve_variable(&self, reference: &str) -> Result<String> {
+        Ok(json_value_to_string(&self.resolve_value(reference)?))
+    }
+
+    /// Resolve the contents of a `{{ }}` reference: the first source with a
+    /// value, passed through its filters
+    fn resolve_value(&self, reference: &str) -> Result<Value> {
+        let reference = reference.trim();
+        let interpolation = parse_interpolation(reference)
+            .with_context(|| format!("Invalid reference '{{{{{}}}}}'", reference))?;
+
+        let mut value = None;
+        for source in &interpolation.sources {
+            value = match source {
+                InterpolationSource::Variable(name) => self.lookup_variable(name)?,
+                InterpolationSource::Literal(text) => Some(Value::String(text.clone())),
+            };
+            if value.is_some() {
+                break;
+            }
+        }
+
+        for filter in &interpolation.filters {
+            value = match (filter.name.as_str(), value) {
+                ("default", value) => Some(
+                    value
+                        .filter(|v| !matches!(v, Value::Null) && v != "")
+                        .unwrap_or_else(|| Value::String(filter.args[0].clone())),
+                ),
+                (_, None) => None,
+                (name, Some(value)) => Some(Value::String(functions::filter(name, &value)?)),
+            };
+        }
+
+        match value {
+            Some(value) => Ok(value),
+            None => self.unresolved(reference),
+        }
+    }
+
+    /// Look up a variable or dynamic variable, or `None` if nothing defines it
+    fn lookup_variable(&self, var_name: &str) -> Result<Option<Value>> {
+        if let Some(name) = var_name.strip_prefix('$') {
+            let value = self
+                .dynamic
+                .resolve(name)
+                .with_context(|| format!("Invalid dynamic variable '{{{{{}}}}}'", var_name))?;
+            // Otherwise it's an explicit env var reference
+            return Ok(value.or_else(|| std::env::var(name).ok()).map(Value::String));
+        }
+
+        if let Some(value) = self.variables.get(var_name) {
+            return Ok(Some(value.clone()));
+        }
+
+        // Fall back to an allowed environment variable
+        Ok(std::env::var(var_name)
+            .ok()
+            .filter(|_| self.env_allowed(var_name))
+            .map(Value::String))
+    }
+
+    /// Value used for a variable nothing defines: an error in strict mode,
+    /// otherwise an empty string
+    fn unresolved(&self, var_name: &str) -> Result<Value> {
+        if self.config.execution.strict_vars {
+            anyhow::bail!(
+                "Unresolved variable '{{{{{}}}}}' (define it in the environment, [pre-request] or with --var)",
+                var_name
+            );
+        }
+        Ok(Value::String(String::new()))
+    }
+
+    /// Whether `{{name}}` may implicitly read the OS environment variable
+    fn env_allowed(&self, name: &str) -> bool {
+        match &self.config.execution.env_allowlist {
+            None => true,
+            Some(patterns) => patterns.iter().any(|pattern| {
+                glob::Pattern::new(pattern).map_or(pattern == name, |p| p.matches(name))
+            }),
+        }
+    }
+
+    /// Environment variables a script sees as `env`
+    fn script_env(&self) -> HashMap<String, String> {
+        std::env::vars()
+            .filter(|(name, _)| self.env_allowed(name))
+            .collect()
+    }
+
+    /// Interpolate strings inside a JSON value. A string that is exactly one
+    /// `{{ }}` reference becomes the variable's typed value.
     fn interpolate_json(&self, json: &serde_json::Value) -> Result<serde_json::Value> {
         match json {
-            serde_json::Value::String(s) => {
-                Ok(serde_json::Value::String(self.interpolate_string(s)?))
-            }
+            serde_json::Value::String(s) => match whole_reference(s) {
+                Some(reference) => self.resolve_value(reference),
+                None => Ok(serde_json::Value::String(self.interpolate_string(s)?)),
+            },
             serde_json::Value::Array(arr) => {
                 let new_arr: Result<Vec<_>> = arr.iter().map(|v| self.interpolate_json(v)).collect();
                 Ok(serde_json::Value::Array(new_arr?))
@@ -118,13 +409,49 @@ impl ExecutionContext {
         }
     }
 
+    /// Check the response to the `attempt`th sending against the `[retry]
+    /// until` condition. Returns `None` when the request has no `[retry]`
+    /// section.
+    pub fn check_until(
+        &self,
+        reqx_file: &ReqxFile,
+        response: &Response,
+        attempt: usize,
+    ) -> Option<AssertionResult> {
+        let retry = reqx_file.retry.as_ref()?;
+        let check = Evaluator::new(response, &self.variables)
+            .loose(self.config.execution.loose_assertions)
+            .schemas(&self.schemas)
+            .check(&retry.parsed);
+        let actual = check.actual.as_ref().map(json_value_to_string);
+
+        Some(AssertionResult {
+            expression: retry.until.clone(),
+            expected: describe_expectation(&retry.parsed),
+            message: format!(
+                "[retry] until {}: did not hold after {} attempt(s), got {}",
+                retry.until,
+                attempt,
+                actual.as_deref().unwrap_or("nothing")
+            ),
+            actual,
+            passed: check.passed,
+            pointer: None,
+            diff: Vec::new(),
+        })
+    }
+
     /// Run assertions against a response
     pub fn run_assertions(&self, reqx_file: &ReqxFile, response: &Response) -> Vec<AssertionResult> {
         let mut results = Vec::new();
 
         for assertion in &reqx_file.assertions {
-            let result = self.evaluate_assertion(assertion, response);
-            results.push(result);
+            match &reqx_file.snapshot {
+                Some(section) if is_snapshot_check(&assertion.parsed) => {
+                    results.push(self.check_snapshot(assertion, section, response));
+                }
+                _ => results.extend(self.evaluate_assertion(assertion, response)),
+            }
         }
 
         results
@@ -134,209 +461,349 @@ impl ExecutionContext {
         &self,
         assertion: &crate::parser::Assertion,
         response: &Response,
-    ) -> AssertionResult {
-        let expression = &assertion.expression;
-        let expected = &assertion.expected;
-
-        // Handle special case: status
-        if expression == "status" {
-            let actual = response.status.to_string();
-            let passed = actual == *expected;
-            return AssertionResult {
-                expression: expression.clone(),
-                expected: expected.clone(),
-                actual: Some(actual),
-                passed,
-                message: if passed {
-                    format!("status = {}", expected)
-                } else {
-                    format!("status: expected {}, got {}", expected, response.status)
-                },
-            };
+    ) -> Vec<AssertionResult> {
+        let evaluator = Evaluator::new(response, &self.variables)
+            .loose(self.config.execution.loose_assertions)
+            .schemas(&self.schemas);
+        let check = evaluator.check(&assertion.parsed);
+        let actual = check.actual.as_ref().map(json_value_to_string);
+        // Show durations and sizes with their unit, e.g. `< 500ms`
+        let unit = match &assertion.parsed {
+            Expression::BinaryOp { left, .. } => match left.as_ref() {
+                Expression::Path(path) => path.quantity().map_or("", |quantity| quantity.unit()),
+                _ => "",
+            },
+            _ => "",
+        };
+        let expectation = describe_expectation(&assertion.parsed);
+        let expected = json_value_to_string(&assertion.expected);
+
+        // Report each schema error on its own, located by JSON pointer
+        if !check.violations.is_empty() {
+            return check
+                .violations
+                .into_iter()
+                .map(|violation| AssertionResult {
+                    expression: assertion.expression.clone(),
+                    expected: expected.clone(),
+                    actual: actual.clone(),
+                    passed: false,
+                    message: if violation.pointer.is_empty() {
+                        format!("{}: {}", assertion.expression, violation.message)
+                    } else {
+                        format!(
+                            "{} at {}: {}",
+                            assertion.expression, violation.pointer, violation.message
+                        )
+                    },
+                    pointer: Some(violation.pointer),
+                    diff: Vec::new(),
+                })
+                .collect();
         }
 
-        // Handle body assertions
-        if expression == "body" || expression.starts_with("body.") || expression.starts_with("body[") {
-            return self.evaluate_body_assertion(expression, expected, response);
-        }
-
-        // Handle header assertions
-        if expression.starts_with("headers.") {
-            let header_name = expression.strip_prefix("headers.").unwrap();
-            let actual = response.headers.get(header_name).cloned();
-            let passed = actual.as_deref() == Some(expected);
-            return AssertionResult {
-                expression: expression.clone(),
-                expected: expected.clone(),
-                actual,
-                passed,
-                message: if passed {
-                    format!("{} = {}", expression, expected)
-                } else {
+        let message = if check.passed {
+            format!("{} {}{}", assertion.expression, expectation, unit)
+        } else if !check.failures.is_empty() {
+            // Name each element of a wildcard path that broke the rule
+            check
+                .failures
+                .iter()
+                .map(|failure| {
                     format!(
-                        "{}: expected {}, got {:?}",
-                        expression,
-                        expected,
-                        actual.unwrap_or_default()
+                        "{}: expected {}, got {}",
+                        failure.path,
+                        expectation,
+                        failure
+                            .actual
+                            .as_ref()
+                            .map(json_value_to_string)
+                            .unwrap_or_else(|| "nothing".to_string())
                     )
+                })
+                .collect::<Vec<_>>()
+                .join("; ")
+        } else {
+            format!(
+                "{}: expected {}{}, got {}",
+                assertion.expression,
+                expectation,
+                unit,
+                actual
+                    .as_ref()
+                    .map_or_else(|| "nothing".to_string(), |actual| format!("{}{}", actual, unit))
+            )
+        };
+
+        // Show how mismatched objects and arrays differ
+        let diff = match (&assertion.parsed, &check.actual) {
+            (
+                Expression::BinaryOp {
+                    op: BinaryOperator::Equals,
+                    right,
+                    ..
                 },
-            };
-        }
+                Some(actual),
+            ) if !check.passed => match right.as_ref() {
+                Expression::Literal(Literal::Json(expected)) => diff::diff(expected, actual),
+                _ => Vec::new(),
+            },
+            _ => Vec::new(),
+        };
 
-        AssertionResult {
-            expression: expression.clone(),
-            expected: expected.clone(),
-            actual: None,
-            passed: false,
-            message: format!("Unknown assertion expression: {}", expression),
-        }
+        vec![AssertionResult {
+            expression: assertion.expression.clone(),
+            expected,
+            actual,
+            passed: check.passed,
+            message,
+            pointer: None,
+            diff,
+        }]
     }
 
-    fn evaluate_body_assertion(
+    fn check_snapshot(
         &self,
-        expression: &str,
-        expected: &str,
+        assertion: &crate::parser::Assertion,
+        section: &SnapshotSection,
         response: &Response,
     ) -> AssertionResult {
-        // Simple body assertion
-        if expression == "body" {
-            let passed = match expected {
-                "is_array" => response.body.is_array(),
-                "is_object" => response.body.is_object(),
-                "is_string" => response.body.is_string(),
-                "is_number" => response.body.is_number(),
-                "exists" => !response.body.is_null(),
-                _ => false,
-            };
-            return AssertionResult {
-                expression: expression.to_string(),
-                expected: expected.to_string(),
-                actual: Some(format!("{:?}", response.body)),
-                passed,
-                message: if passed {
-                    format!("body {}", expected)
-                } else {
-                    format!("body: expected {}", expected)
-                },
-            };
+        let mut redacted = response.clone();
+        for path in &section.ignore {
+            snapshot::redact(&mut redacted.body, &path.segments);
         }
 
-        // JSONPath-like assertion
-        let path = expression.strip_prefix("body").unwrap_or(expression);
-        let value = extract_json_path(&response.body, path);
-
-        let (passed, actual) = match value {
-            Some(v) => {
-                let actual_str = json_value_to_string(&v);
-                let passed = match expected {
-                    "exists" => true,
-                    "is_array" => v.is_array(),
-                    "is_object" => v.is_object(),
-                    "is_string" => v.is_string(),
-                    "is_number" => v.is_number(),
-                    "is_uuid" => is_uuid(&actual_str),
-                    "is_iso8601" => is_iso8601(&actual_str),
-                    _ => actual_str == *expected,
-                };
-                (passed, Some(actual_str))
-            }
-            None => {
-                let passed = expected == "!exists";
-                (passed, None)
-            }
+        let actual = match &assertion.parsed {
+            Expression::FunctionCall { args, .. } => args
+                .first()
+                .and_then(|subject| Evaluator::new(&redacted, &self.variables).evaluate(subject)),
+            _ => None,
+        }
+        .unwrap_or(serde_json::Value::Null);
+
+        // Each data row gets its own snapshot
+        let key = match &self.iteration {
+            Some(iteration) => format!("{} [{}]", assertion.expression, iteration),
+            None => assertion.expression.clone(),
+        };
+        let file = section.file.display();
+        let outcome = snapshot::compare(
+            &section.file,
+            &key,
+            &actual,
+            self.update_snapshots,
+        );
+        let (passed, message, diff) = match outcome {
+            Ok(SnapshotOutcome::Recorded) => (
+                true,
+                format!("{} snapshot recorded in {}", assertion.expression, file),
+                Vec::new(),
+            ),
+            Ok(SnapshotOutcome::Updated) => (
+                true,
+                format!("{} snapshot updated in {}", assertion.expression, file),
+                Vec::new(),
+            ),
+            Ok(SnapshotOutcome::Matched) => (
+                true,
+                format!("{} matches snapshot", assertion.expression),
+                Vec::new(),
+            ),
+            Ok(SnapshotOutcome::Mismatched(diff)) => (
+                false,
+                format!(
+                    "{}: does not match snapshot in {} ({} difference(s), run with --update-snapshots to accept)",
+                    assertion.expression,
+                    file,
+                    diff.len()
+                ),
+                diff,
+            ),
+            Err(e) => (false, format!("{}: {:#}", assertion.expression, e), Vec::new()),
         };
 
         AssertionResult {
-            expression: expression.to_string(),
-            expected: expected.to_string(),
-            actual,
+            expression: assertion.expression.clone(),
+            expected: json_value_to_string(&assertion.expected),
+            actual: Some(json_value_to_string(&actual)),
             passed,
-            message: if passed {
-                format!("{} = {}", expression, expected)
-            } else {
-                format!("{}: expected {}", expression, expected)
-            },
+            message,
+            pointer: None,
+            diff,
         }
     }
 
-    /// Run post-response scripts
-    pub fn run_post_response(&mut self, reqx_file: &ReqxFile, response: &Response) -> Result<()> {
-        for script in &reqx_file.post_response {
-            let value = self.evaluate_expression(&script.expression, response)?;
-            self.variables.insert(script.variable.clone(), value);
+    /// Why `[meta] skip_if` or `run_if` rules the request out, or `None` if
+    /// it should run
+    pub fn skip_reason(&self, reqx_file: &ReqxFile) -> Result<Option<String>> {
+        let meta = &reqx_file.meta;
+
+        if let Some(skip_if) = &meta.skip_if {
+            if self.condition_holds(&skip_if.parsed)? {
+                return Ok(Some(format!("skip_if {}", skip_if.expression)));
+            }
+        }
+        if let Some(run_if) = &meta.run_if {
+            if !self.condition_holds(&run_if.parsed)? {
+                return Ok(Some(format!("run_if {} does not hold", run_if.expression)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Evaluate a condition against the variables and environment name.
+    /// Values compare by their string form, since `--var` values are strings.
+    fn condition_holds(&self, condition: &Condition) -> Result<bool> {
+        Ok(match condition {
+            Condition::Truthy(operand) => is_truthy(&self.operand_value(operand)?),
+            Condition::Compare { left, op, right } => evaluator::compare(
+                &self.operand_value(left)?,
+                *op,
+                &self.operand_value(right)?,
+                true,
+            ),
+        })
+    }
+
+    fn operand_value(&self, operand: &Operand) -> Result<Value> {
+        Ok(match operand {
+            Operand::Variable(name) => self.lookup_variable(name)?.unwrap_or(Value::Null),
+            Operand::Environment => self
+                .environment
+                .clone()
+                .map(Value::String)
+                .unwrap_or(Value::Null),
+            Operand::Literal(literal) => evaluator::literal_to_json(literal),
+        })
+    }
+
+    /// Compute `[pre-request]` variables in order, so each can use those
+    /// before it and the request can use them all
+    pub fn run_pre_request(&mut self, reqx_file: &ReqxFile) -> Result<()> {
+        for variable in &reqx_file.pre_request {
+            let value = self.compute(&variable.parsed).with_context(|| {
+                format!("Failed to compute '{}' in [pre-request]", variable.variable)
+            })?;
+            self.variables
+                .insert(variable.variable.clone(), Value::String(value));
         }
         Ok(())
     }
 
-    fn evaluate_expression(&self, expression: &str, response: &Response) -> Result<String> {
-        // Handle res.body.* expressions
-        if expression.starts_with("res.body") {
-            let path = expression.strip_prefix("res.body").unwrap_or("");
-            if let Some(value) = extract_json_path(&response.body, path) {
-                return Ok(json_value_to_string(&value));
+    fn compute(&self, computed: &Computed) -> Result<String> {
+        match computed {
+            Computed::Template(template) => self.interpolate_string(template),
+            Computed::Call { function, args } => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.compute(arg))
+                    .collect::<Result<Vec<_>>>()?;
+                functions::call(function, &args)
             }
         }
+    }
+
+    /// Run the `[pre-request]` script, which may change the interpolated
+    /// request and set variables
+    pub fn run_pre_request_script(&mut self, reqx_file: &mut ReqxFile) -> Result<()> {
+        let Some(script) = &reqx_file.pre_request_script else {
+            return Ok(());
+        };
 
-        // Handle res.status
-        if expression == "res.status" {
-            return Ok(response.status.to_string());
+        let outcome = script::run(script, reqx_file, None, &self.variables, &self.script_env())?;
+        if !outcome.failures.is_empty() {
+            anyhow::bail!("{}", outcome.failures.join("; "));
         }
+        script::apply_request(reqx_file, &outcome.request)?;
+        self.apply_script_variables(outcome.variables, outcome.removed);
+        Ok(())
+    }
 
-        // Handle res.headers.*
-        if expression.starts_with("res.headers.") {
-            let header = expression.strip_prefix("res.headers.").unwrap();
-            if let Some(value) = response.headers.get(header) {
-                return Ok(value.clone());
-            }
+    /// Apply the variables a script set and removed, leaving the rest as
+    /// they were
+    fn apply_script_variables(&mut self, changed: HashMap<String, Value>, removed: Vec<String>) {
+        for name in removed {
+            self.variables.remove(&name);
         }
+        self.variables.extend(changed);
+    }
 
-        // Handle pipe expressions (e.g., "res.body.data | length")
-        if expression.contains(" | ") {
-            let parts: Vec<&str> = expression.split(" | ").collect();
-            if parts.len() == 2 {
-                let base_value = self.evaluate_expression(parts[0].trim(), response)?;
-                return self.apply_function(parts[1].trim(), &base_value);
+    /// Run the `[post-response]` script, returning its failures as assertion
+    /// results
+    pub fn run_post_response_script(
+        &mut self,
+        reqx_file: &ReqxFile,
+        response: &Response,
+    ) -> Vec<AssertionResult> {
+        let Some(script) = &reqx_file.post_response_script else {
+            return Vec::new();
+        };
+
+        let env = self.script_env();
+        let failures = match script::run(script, reqx_file, Some(response), &self.variables, &env) {
+            Ok(outcome) => {
+                self.apply_script_variables(outcome.variables, outcome.removed);
+                outcome.failures
             }
+            Err(e) => vec![format!("script error: {}", e)],
+        };
+
+        failures
+            .into_iter()
+            .map(|message| AssertionResult {
+                expression: "[post-response] script".to_string(),
+                expected: String::new(),
+                actual: None,
+                passed: false,
+                message,
+                pointer: None,
+                diff: Vec::new(),
+            })
+            .collect()
+    }
+
+    /// Run post-response scripts
+    pub fn run_post_response(&mut self, reqx_file: &ReqxFile, response: &Response) -> Result<()> {
+        for script in &reqx_file.post_response {
+            let value = Evaluator::new(response, &self.variables)
+                .evaluate(&script.parsed)
+                .unwrap_or_else(|| Value::String(String::new()));
+            self.variables.insert(script.variable.clone(), value);
         }
 
-        Ok(String::new())
+        let persisted: Vec<&str> = reqx_file
+            .post_response
+            .iter()
+            .map(|script| script.variable.as_str())
+            .filter(|name| self.config.state.persist.iter().any(|p| p == name))
+            .collect();
+        if let (Some(path), false) = (&self.state_file, persisted.is_empty()) {
+            self.save_state(path, &persisted)?;
+        }
+        Ok(())
     }
 
-    fn apply_function(&self, func: &str, value: &str) -> Result<String> {
-        match func {
-            "length" => {
-                if let Ok(json) = serde_json::from_str::<serde_json::Value>(value) {
-                    if let Some(arr) = json.as_array() {
-                        return Ok(arr.len().to_string());
-                    }
-                    if let Some(s) = json.as_str() {
-                        return Ok(s.len().to_string());
-                    }
-                }
-                Ok(value.len().to_string())
-            }
-            "first" => {
-                if let Ok(json) = serde_json::from_str::<serde_json::Value>(value) {
-                    if let Some(arr) = json.as_array() {
-                        if let Some(first) = arr.first() {
-                            return Ok(json_value_to_string(first));
-                        }
-                    }
-                }
-                Ok(String::new())
-            }
-            "last" => {
-                if let Ok(json) = serde_json::from_str::<serde_json::Value>(value) {
-                    if let Some(arr) = json.as_array() {
-                        if let Some(last) = arr.last() {
-                            return Ok(json_value_to_string(last));
-                        }
-                    }
-                }
-                Ok(String::new())
+    /// Save variables to the state file, with the TTL configured for each in
+    /// `[state]`
+    fn save_state(&self, path: &Path, names: &[&str]) -> Result<()> {
+        let mut state = State::load(path)?;
+        let now = chrono::Utc::now();
+
+        for name in names {
+            let ttl = match self.config.state.ttl.get(*name) {
+                Some(ttl) => Some(Duration::from_millis(
+                    parse_duration(ttl)
+                        .with_context(|| format!("Invalid ttl for '{}' in [state]", name))?,
+                )),
+                None => None,
+            };
+            if let Some(value) = self.variables.get(*name) {
+                state.set(name, value, ttl, now);
             }
-            _ => Ok(value.to_string()),
         }
+
+        state.save(path)
     }
 }
 
@@ -347,6 +814,12 @@ pub struct AssertionResult {
     pub actual: Option<String>,
     pub passed: bool,
     pub message: String,
+    /// JSON pointer to the failing value within the asserted one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pointer: Option<String>,
+    /// Differences between an expected and actual object or array
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub diff: Vec<DiffEntry>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -359,89 +832,157 @@ pub struct ExecutionResult {
     pub assertions: Vec<AssertionResult>,
     pub failed: bool,
     pub error: Option<String>,
+    /// Why the request was not sent, if it was skipped
+    #[serde(default)]
+    pub skipped: Option<String>,
+    /// Data row the request ran with, when run once per row
+    #[serde(default)]
+    pub iteration: Option<String>,
+    /// Each sending of a request with `[retry] until`, in order
+    #[serde(default)]
+    pub attempts: Vec<Attempt>,
 }
 
-// Helper functions
+/// How a request ended, for counting results
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Passed,
+    Failed,
+    Skipped,
+}
 
-fn extract_json_path<'a>(json: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
-    if path.is_empty() {
-        return Some(json);
+/// One sending of a request polled with `[retry] until`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attempt {
+    /// When the attempt was sent, from the start of the first one
+    pub started: Duration,
+    pub duration: Duration,
+    pub status: u16,
+    /// Whether the `until` condition held for the response
+    pub matched: bool,
+}
+
+impl ExecutionResult {
+    /// Result for a request that was never sent
+    pub fn not_run(file: &Path, method: &str, url: &str, duration: Duration) -> Self {
+        Self {
+            file: file.to_path_buf(),
+            method: method.to_string(),
+            url: url.to_string(),
+            status: None,
+            duration,
+            assertions: vec![],
+            failed: false,
+            error: None,
+            skipped: None,
+            iteration: None,
+            attempts: vec![],
+        }
     }
 
-    let path = path.trim_start_matches('.');
-    let mut current = json;
+    pub fn skipped(mut self, reason: String) -> Self {
+        self.skipped = Some(reason);
+        self
+    }
 
-    for segment in split_path(path) {
-        match segment {
-            PathSegment::Property(name) => {
-                current = current.get(&name)?;
-            }
-            PathSegment::Index(idx) => {
-                current = current.get(idx)?;
-            }
-            PathSegment::Wildcard => {
-                // Return first element for wildcard
-                if let Some(arr) = current.as_array() {
-                    current = arr.first()?;
-                } else {
-                    return None;
-                }
-            }
+    /// Mark the result failed with an error
+    pub fn error(mut self, message: String) -> Self {
+        self.failed = true;
+        self.error = Some(message);
+        self
+    }
+
+    /// Skipped requests are neither passed nor failed
+    pub fn outcome(&self) -> Outcome {
+        match (&self.skipped, self.failed) {
+            (Some(_), _) => Outcome::Skipped,
+            (None, true) => Outcome::Failed,
+            (None, false) => Outcome::Passed,
         }
     }
 
-    Some(current)
+    /// Name for reports: the file name, followed by the data row if any
+    pub fn name(&self) -> String {
+        let file = self
+            .file
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("unknown");
+        match &self.iteration {
+            Some(iteration) => format!("{} [{}]", file, iteration),
+            None => file.to_string(),
+        }
+    }
 }
 
-enum PathSegment {
-    Property(String),
-    Index(usize),
-    Wildcard,
-}
+// Helper functions
 
-fn split_path(path: &str) -> Vec<PathSegment> {
-    let mut segments = Vec::new();
-    let mut current = String::new();
-    let mut in_bracket = false;
-
-    for c in path.chars() {
-        match c {
-            '.' if !in_bracket => {
-                if !current.is_empty() {
-                    segments.push(PathSegment::Property(current.clone()));
-                    current.clear();
-                }
+/// Describe what an assertion expects, e.g. `> 10` or `is_uuid`
+fn describe_expectation(expression: &Expression) -> String {
+    match expression {
+        Expression::BinaryOp { op, right, .. } => match right.as_ref() {
+            Expression::Literal(literal) => format!("{} {}", op.as_str(), literal),
+            Expression::Variable(name) => format!("{} {{{{{}}}}}", op.as_str(), name),
+            _ => op.as_str().to_string(),
+        },
+        Expression::FunctionCall { name, args } if name == SCHEMA_FUNCTION => {
+            match args.get(1) {
+                Some(Expression::Literal(schema)) => format!("schema {}", schema),
+                _ => "schema".to_string(),
             }
-            '[' => {
-                if !current.is_empty() {
-                    segments.push(PathSegment::Property(current.clone()));
-                    current.clear();
-                }
-                in_bracket = true;
+        }
+        Expression::FunctionCall { name, .. } => name.clone(),
+        Expression::ForEach { quantifier, check } => match quantifier {
+            Quantifier::All => describe_expectation(check),
+            other => format!("{} {}", other.as_str(), describe_expectation(check)),
+        },
+        _ => "true".to_string(),
+    }
+}
+
+/// Whether a condition operand counts as true
+fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null | Value::Bool(false) => false,
+        Value::String(s) => !matches!(s.as_str(), "" | "false" | "0"),
+        Value::Number(n) => n.as_f64() != Some(0.0),
+        _ => true,
+    }
+}
+
+/// The inner text of a string that is a single `{{ }}` reference
+/// Byte range of the first `{{ }}` reference, including the braces of any
+/// references nested inside it
+fn find_reference(input: &str) -> Option<std::ops::Range<usize>> {
+    let bytes = input.as_bytes();
+    let start = input.find("{{")?;
+    let mut depth = 0;
+    let mut i = start;
+    while i + 1 < bytes.len() {
+        match &bytes[i..i + 2] {
+            b"{{" => {
+                depth += 1;
+                i += 2;
             }
-            ']' => {
-                if current == "*" {
-                    segments.push(PathSegment::Wildcard);
-                } else if let Ok(idx) = current.parse::<usize>() {
-                    segments.push(PathSegment::Index(idx));
+            b"}}" => {
+                depth -= 1;
+                i += 2;
+                if depth == 0 {
+                    return Some(start..i);
                 }
-                current.clear();
-                in_bracket = false;
-            }
-            _ => {
-                current.push(c);
             }
+            _ => i += 1,
         }
     }
+    None
+}
 
-    if !current.is_empty() {
-        segments.push(PathSegment::Property(current));
-    }
-
-    segments
+fn whole_reference(s: &str) -> Option<&str> {
+    let inner = s.strip_prefix("{{")?.strip_suffix("}}")?;
+    (!inner.contains("{{") && !inner.contains("}}")).then_some(inner)
 }
 
-fn json_value_to_string(value: &serde_json::Value) -> String {
+pub(crate) fn json_value_to_string(value: &serde_json::Value) -> String {
     match value {
         serde_json::Value::String(s) => s.clone(),
         serde_json::Value::Number(n) => n.to_string(),
@@ -451,17 +992,471 @@ fn json_value_to_string(value: &serde_json::Value) -> String {
     }
 }
 
-fn is_uuid(s: &str) -> bool {
+pub(crate) fn is_uuid(s: &str) -> bool {
     uuid::Uuid::parse_str(s).is_ok()
 }
 
-fn is_iso8601(s: &str) -> bool {
+pub(crate) fn is_iso8601(s: &str) -> bool {
     chrono::DateTime::parse_from_rfc3339(s).is_ok()
         || chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S").is_ok()
 }
 
-fn rand_number() -> u32 {
-    use std::time::{SystemTime, UNIX_EPOCH};
-    let duration = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
-    (duration.as_nanos() % 1_000_000) as u32
+pub(crate) fn is_email(s: &str) -> bool {
+    static EMAIL: OnceLock<Regex> = OnceLock::new();
+    EMAIL
+        .get_or_init(|| Regex::new(r"^[^@\s]+@[^@\s]+\.[^@\s]+$").unwrap())
+        .is_match(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_content;
+    use std::path::Path;
+
+    fn context() -> ExecutionContext {
+        let mut context = ExecutionContext::new(Config::default());
+        context.set_variable("user_name".to_string(), "Alice".to_string());
+        context.set_variable("min_total".to_string(), "10".to_string());
+        context.set_variable("index".to_string(), "1".to_string());
+        context
+    }
+
+    #[test]
+    fn test_interpolate_assertion_expectations() {
+        let content = r#"
+[request]
+method = "GET"
+url = "https://api.example.com/users"
+
+[assert]
+"body.name" = "{{user_name}}"
+"body.total" = "> {{min_total}}"
+"body.users[{{index}}].id" = "exists"
+"#;
+
+        let reqx_file = parse_content(content, Path::new("test.reqx")).unwrap();
+        let interpolated = context().interpolate(&reqx_file).unwrap();
+
+        let response = Response::test_json(
+            200,
+            serde_json::json!({
+                "name": "Alice",
+                "total": 11,
+                "users": [{ "id": 1 }, { "id": 2 }]
+            }),
+        );
+
+        let results = context().run_assertions(&interpolated, &response);
+        assert!(results.iter().all(|r| r.passed), "{:?}", results);
+        let name = results.iter().find(|r| r.expression == "body.name").unwrap();
+        assert_eq!(name.expected, "Alice");
+    }
+
+    #[test]
+    fn test_pre_request_variables_reach_request() {
+        let content = r#"
+[request]
+method = "GET"
+url = "https://api.example.com/users/{{user}}"
+
+[headers]
+X-Signature = "{{sig}}"
+
+[pre-request]
+user = "lower({{user_name}})"
+sig = "sha256('{{user}}')"
+"#;
+        let reqx_file = parse_content(content, Path::new("test.reqx")).unwrap();
+
+        let mut context = context();
+        context.run_pre_request(&reqx_file).unwrap();
+        let interpolated = context.interpolate(&reqx_file).unwrap();
+
+        assert_eq!(interpolated.request.url, "https://api.example.com/users/alice");
+        assert_eq!(
+            interpolated.headers["X-Signature"],
+            functions::sha256("alice")
+        );
+    }
+
+    #[test]
+    fn test_typed_variables_pass_through_json_body() {
+        let content = r#"
+[request]
+method = "POST"
+url = "https://api.example.com/orders"
+
+[body]
+owner = "{{user}}"
+quantity = "{{count}}"
+note = "{{count}} items for {{user_name}}"
+
+[post-response]
+user = "body.user"
+"#;
+        let reqx_file = parse_content(content, Path::new("test.reqx")).unwrap();
+        let response = Response::test_json(
+            200,
+            serde_json::json!({ "user": { "id": 7, "admin": false } }),
+        );
+
+        let mut context = context();
+        context.set_variable("count".to_string(), 3);
+        context.run_post_response(&reqx_file, &response).unwrap();
+        assert_eq!(
+            context.variables["user"],
+            serde_json::json!({ "id": 7, "admin": false })
+        );
+
+        let interpolated = context.interpolate(&reqx_file).unwrap();
+        match interpolated.body {
+            Some(crate::parser::BodySection::Json(body)) => assert_eq!(
+                body,
+                serde_json::json!({
+                    "owner": { "id": 7, "admin": false },
+                    "quantity": 3,
+                    "note": "3 items for Alice"
+                })
+            ),
+            other => panic!("expected JSON body, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_state_is_saved_and_environment_takes_precedence() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state/local.toml");
+        let mut config = Config::default();
+        config.state.persist = vec!["token".to_string(), "user_id".to_string()];
+
+        let content = r#"
+[request]
+method = "POST"
+url = "https://api.example.com/login"
+
+[post-response]
+token = "body.token"
+user_id = "body.id"
+"#;
+        let reqx_file = parse_content(content, Path::new("test.reqx")).unwrap();
+        let response = Response::test_json(200, serde_json::json!({ "token": "saved", "id": 7 }));
+        let mut first = ExecutionContext::new(config.clone());
+        first.load_state(path.clone()).unwrap();
+        first.run_post_response(&reqx_file, &response).unwrap();
+
+        config.variables.insert("token".to_string(), "edited".to_string());
+        let mut next = ExecutionContext::new(config);
+        next.load_state(path.clone()).unwrap();
+        assert_eq!(next.variables["token"], "edited");
+        assert_eq!(next.variables["user_id"], 7);
+
+        std::fs::write(&path, "not toml [").unwrap();
+        assert!(ExecutionContext::new(next.config.clone()).load_state(path).is_err());
+    }
+
+    #[test]
+    fn test_script_leaves_untouched_variables_alone() {
+        let content = r#"
+[request]
+method = "GET"
+url = "https://api.example.com/users"
+
+[pre-request]
+script = "vars.page = 2;"
+"#;
+        let mut reqx_file = parse_content(content, Path::new("test.reqx")).unwrap();
+        let user = serde_json::json!({ "id": u64::MAX, "scores": [1.0, null], "tags": {} });
+
+        let mut context = context();
+        context.set_variable("user".to_string(), user.clone());
+        context.run_pre_request_script(&mut reqx_file).unwrap();
+
+        assert_eq!(context.variables["user"], user);
+        assert_eq!(context.variables["page"], 2);
+    }
+
+    #[test]
+    fn test_skip_if_and_run_if() {
+        let content = r#"
+[meta]
+skip_if = "env == 'prod'"
+run_if = "{{role}} == 'admin'"
+
+[request]
+method = "DELETE"
+url = "https://api.example.com/users/1"
+"#;
+        let reqx_file = parse_content(content, Path::new("test.reqx")).unwrap();
+
+        let mut context = context();
+        context.set_variable("role".to_string(), "admin".to_string());
+        context.environment = Some("staging".to_string());
+        assert_eq!(context.skip_reason(&reqx_file).unwrap(), None);
+
+        context.environment = Some("prod".to_string());
+        assert_eq!(
+            context.skip_reason(&reqx_file).unwrap().as_deref(),
+            Some("skip_if env == 'prod'")
+        );
+
+        context.environment = None;
+        context.set_variable("role".to_string(), "viewer".to_string());
+        assert!(context
+            .skip_reason(&reqx_file)
+            .unwrap()
+            .unwrap()
+            .starts_with("run_if"));
+    }
+
+    #[test]
+    fn test_strict_vars_names_unresolved_variable() {
+        let content = r#"
+[request]
+method = "GET"
+url = "https://api.example.com/me"
+
+[headers]
+Authorization = "Bearer {{acess_token}}"
+"#;
+        let reqx_file = parse_content(content, Path::new("test.reqx")).unwrap();
+
+        let lenient = context().interpolate(&reqx_file).unwrap();
+        assert_eq!(lenient.headers["Authorization"], "Bearer ");
+
+        let mut config = Config::default();
+        config.execution.strict_vars = true;
+        let err = ExecutionContext::new(config)
+            .interpolate(&reqx_file)
+            .unwrap_err();
+        let message = format!("{:#}", err);
+        assert!(message.contains("header 'Authorization'"), "{}", message);
+        assert!(message.contains("{{acess_token}}"), "{}", message);
+    }
+
+    #[test]
+    fn test_interpolate_filters_and_fallbacks() {
+        let mut context = context();
+        context.set_variable("token".to_string(), "user:pass".to_string());
+        context.set_variable("empty".to_string(), String::new());
+        context.set_variable("query".to_string(), "a b&c".to_string());
+
+        let render = |input: &str| context.interpolate_string(input).unwrap();
+        assert_eq!(render("{{user_name | upper}}"), "ALICE");
+        assert_eq!(render("{{token | base64}}"), "dXNlcjpwYXNz");
+        assert_eq!(render("?q={{query | urlencode}}"), "?q=a+b%26c");
+        assert_eq!(render("{{user_name | json}}"), r#""Alice""#);
+        assert_eq!(render(r#"{{user_id | default: "1"}}"#), "1");
+        assert_eq!(render(r#"{{empty | default: "1"}}"#), "1");
+        assert_eq!(render("{{user_id ?? user_name}}"), "Alice");
+        assert_eq!(render(r#"{{user_id ?? "anon" | upper}}"#), "ANON");
+        assert_eq!(
+            render("Basic {{$base64({{user_name}}:{{min_total}})}}"),
+            "Basic QWxpY2U6MTA="
+        );
+        assert_eq!(render("{{user_name}} {{ unclosed"), "Alice {{ unclosed");
+
+        let mut config = Config::default();
+        config.execution.strict_vars = true;
+        let strict = ExecutionContext::new(config);
+        assert_eq!(
+            strict
+                .interpolate_string(r#"{{user_id | default: "1"}}"#)
+                .unwrap(),
+            "1"
+        );
+        assert!(strict.interpolate_string("{{user_id ?? other_id}}").is_err());
+        assert!(context.interpolate_string("{{user_name | shout}}").is_err());
+    }
+
+    #[test]
+    fn test_env_allowlist_limits_implicit_env_vars() {
+        std::env::set_var("REQX_TEST_ALLOWED", "yes");
+        std::env::set_var("REQX_TEST_SECRET", "hunter2");
+
+        let mut config = Config::default();
+        config.execution.env_allowlist = Some(vec!["REQX_TEST_ALLOW*".to_string()]);
+        let context = ExecutionContext::new(config);
+
+        assert_eq!(context.resolve_variable("REQX_TEST_ALLOWED").unwrap(), "yes");
+        assert_eq!(context.resolve_variable("REQX_TEST_SECRET").unwrap(), "");
+        // Explicit references are not implicit reads
+        assert_eq!(context.resolve_variable("$REQX_TEST_SECRET").unwrap(), "hunter2");
+
+        std::env::remove_var("REQX_TEST_ALLOWED");
+        std::env::remove_var("REQX_TEST_SECRET");
+    }
+
+    #[test]
+    fn test_env_allowlist_limits_script_env() {
+        std::env::set_var("REQX_SCRIPT_ALLOWED", "yes");
+        std::env::set_var("REQX_SCRIPT_SECRET", "hunter2");
+
+        let content = r#"
+[request]
+method = "GET"
+url = "https://api.example.com/users"
+
+[pre-request]
+script = """
+vars.allowed = env.REQX_SCRIPT_ALLOWED;
+vars.leaked = "REQX_SCRIPT_SECRET" in env;
+"""
+"#;
+        let mut reqx_file = parse_content(content, Path::new("test.reqx")).unwrap();
+
+        let mut config = Config::default();
+        config.execution.env_allowlist = Some(vec!["REQX_SCRIPT_ALLOW*".to_string()]);
+        let mut context = ExecutionContext::new(config);
+        context.run_pre_request_script(&mut reqx_file).unwrap();
+
+        assert_eq!(context.variables["allowed"], "yes");
+        assert_eq!(context.variables["leaked"], false);
+
+        std::env::remove_var("REQX_SCRIPT_ALLOWED");
+        std::env::remove_var("REQX_SCRIPT_SECRET");
+    }
+
+    #[test]
+    fn test_failed_object_assertion_carries_diff() {
+        let content = r#"
+[request]
+method = "GET"
+url = "https://api.example.com/users/1"
+
+[assert]
+body = { id = 1, name = "Alice", tags = ["a"] }
+"#;
+        let reqx_file = parse_content(content, Path::new("test.reqx")).unwrap();
+
+        let response = Response::test_json(
+            200,
+            serde_json::json!({ "id": 1, "name": "Bob", "tags": ["a", "b"] }),
+        );
+
+        let results = context().run_assertions(&reqx_file, &response);
+        assert!(!results[0].passed);
+
+        let diff: Vec<_> = results[0]
+            .diff
+            .iter()
+            .map(|entry| (entry.kind, entry.pointer.as_str()))
+            .collect();
+        assert_eq!(
+            diff,
+            vec![(DiffKind::Changed, "/name"), (DiffKind::Added, "/tags/1")]
+        );
+    }
+
+    #[test]
+    fn test_snapshot_is_kept_per_data_row() {
+        let dir = tempfile::tempdir().unwrap();
+        let content = r#"
+[request]
+method = "GET"
+url = "https://api.example.com/users/{{id}}"
+
+[snapshot]
+"#;
+        let reqx_file = parse_content(content, &dir.path().join("user.reqx")).unwrap();
+
+        let mut context = context();
+        for (label, id) in [("alice", 1), ("bob", 2), ("alice", 1)] {
+            let row = DataRow {
+                label: label.to_string(),
+                values: serde_json::Map::from_iter([("id".to_string(), serde_json::json!(id))]),
+            };
+            let response = Response::test_json(200, serde_json::json!({ "id": id }));
+
+            let saved = context.set_row(&row);
+            let results = context.run_assertions(&reqx_file, &response);
+            context.restore_variables(saved);
+            assert!(results.iter().all(|r| r.passed), "{}: {:?}", label, results);
+        }
+
+        let stored = std::fs::read_to_string(dir.path().join("user.snap.json")).unwrap();
+        let stored: Value = serde_json::from_str(&stored).unwrap();
+        assert_eq!(stored["body [alice]"], serde_json::json!({ "id": 1 }));
+        assert_eq!(stored["body [bob]"], serde_json::json!({ "id": 2 }));
+    }
+
+    #[test]
+    fn test_schema_assertion_reports_each_error() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("schemas")).unwrap();
+        std::fs::write(
+            dir.path().join("schemas/user.json"),
+            r#"{
+                "$schema": "http://json-schema.org/draft-07/schema#",
+                "type": "object",
+                "required": ["id", "email"],
+                "properties": { "id": { "type": "string" } }
+            }"#,
+        )
+        .unwrap();
+
+        let content = r#"
+[request]
+method = "GET"
+url = "https://api.example.com/users"
+
+[assert]
+"body.data[0]" = { schema = "schemas/user.json" }
+"body.data[1]" = { schema = "schemas/user.json" }
+"#;
+        let reqx_file = parse_content(content, &dir.path().join("users.reqx")).unwrap();
+
+        let response = Response::test_json(
+            200,
+            serde_json::json!({
+                "data": [{ "id": "u1", "email": "a@example.com" }, { "id": 42 }]
+            }),
+        );
+
+        let results = context().run_assertions(&reqx_file, &response);
+        assert_eq!(results.len(), 3, "{:?}", results);
+        assert!(results[0].passed);
+
+        let pointers: Vec<_> = results[1..].iter().map(|r| r.pointer.as_deref()).collect();
+        assert!(pointers.contains(&Some("/id")));
+        assert!(pointers.contains(&Some("")));
+        assert!(results[1..].iter().all(|r| !r.passed));
+    }
+
+    #[test]
+    fn test_wildcard_schema_assertion_locates_failing_element() {
+        let dir = tempfile::tempdir().unwrap();
+        let schema = dir.path().join("item.json");
+        std::fs::write(
+            &schema,
+            r#"{ "type": "object", "properties": { "id": { "type": "integer" } } }"#,
+        )
+        .unwrap();
+
+        let content = r#"
+[request]
+method = "GET"
+url = "https://api.example.com/items"
+
+[assert]
+"body.items[*]" = { schema = "item.json" }
+"#;
+        let reqx_file = parse_content(content, &dir.path().join("items.reqx")).unwrap();
+        let response = Response::test_json(
+            200,
+            serde_json::json!({ "items": [{ "id": 1 }, { "id": "two" }, { "id": 3 }] }),
+        );
+
+        let context = context();
+        let results = context.run_assertions(&reqx_file, &response);
+        assert_eq!(results.len(), 1, "{:?}", results);
+        assert!(!results[0].passed);
+        assert_eq!(results[0].pointer.as_deref(), Some("/1/id"));
+        assert!(results[0].message.contains("\"two\""), "{}", results[0].message);
+
+        // The compiled schema is reused once the file is gone
+        std::fs::remove_file(&schema).unwrap();
+        let results = context.run_assertions(&reqx_file, &response);
+        assert_eq!(results[0].pointer.as_deref(), Some("/1/id"));
+    }
 }
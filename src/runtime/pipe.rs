@@ -0,0 +1,259 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Functions on the right-hand side of a pipe, e.g.
+//! `res.body.items | filter(.active) | map(.id) | join(",")`
+
+use super::evaluator::literal_to_json;
+use super::json_value_to_string;
+use crate::parser::ast::{PathSegment, PipeArg};
+use serde_json::Value;
+use std::cmp::Ordering;
+
+/// Apply a function from `parser::ast::PIPE_FUNCTIONS`. `None` means the
+/// function does not apply to the value, e.g. `sum` of a string.
+pub fn apply(function: &str, args: &[PipeArg], value: Value) -> Option<Value> {
+    let arg = args.first();
+
+    match function {
+        "length" => Some(Value::from(match &value {
+            Value::Array(items) => items.len(),
+            Value::Object(object) => object.len(),
+            Value::String(s) => s.len(),
+            other => json_value_to_string(other).len(),
+        })),
+        "first" => into_array(value)?.into_iter().next(),
+        "last" => into_array(value)?.pop(),
+        "filter" => Some(Value::Array(
+            into_array(value)?
+                .into_iter()
+                .filter(|item| is_truthy(select(item, arg).as_ref()))
+                .collect(),
+        )),
+        "map" => Some(Value::Array(
+            into_array(value)?
+                .iter()
+                .map(|item| select(item, arg).unwrap_or(Value::Null))
+                .collect(),
+        )),
+        "join" => {
+            let separator = text(arg).unwrap_or_else(|| ",".to_string());
+            let parts: Vec<String> = value.as_array()?.iter().map(json_value_to_string).collect();
+            Some(Value::String(parts.join(&separator)))
+        }
+        "keys" => Some(Value::Array(
+            value
+                .as_object()?
+                .keys()
+                .cloned()
+                .map(Value::String)
+                .collect(),
+        )),
+        "values" => Some(Value::Array(value.as_object()?.values().cloned().collect())),
+        "sum" => {
+            let mut total = 0.0;
+            for item in value.as_array()? {
+                total += select(item, arg)?.as_f64()?;
+            }
+            Some(number(total))
+        }
+        "min" => extreme(value, arg, Ordering::Less),
+        "max" => extreme(value, arg, Ordering::Greater),
+        "sort" => {
+            let mut items = into_array(value)?;
+            items.sort_by(|a, b| order(select(a, arg).as_ref(), select(b, arg).as_ref()));
+            Some(Value::Array(items))
+        }
+        "unique" => {
+            let mut unique = Vec::new();
+            for item in into_array(value)? {
+                if !unique.contains(&item) {
+                    unique.push(item);
+                }
+            }
+            Some(Value::Array(unique))
+        }
+        "reverse" => match value {
+            Value::Array(mut items) => {
+                items.reverse();
+                Some(Value::Array(items))
+            }
+            Value::String(s) => Some(Value::String(s.chars().rev().collect())),
+            _ => None,
+        },
+        "lower" => Some(Value::String(value.as_str()?.to_lowercase())),
+        "upper" => Some(Value::String(value.as_str()?.to_uppercase())),
+        "trim" => Some(Value::String(value.as_str()?.trim().to_string())),
+        "split" => {
+            let separator = text(arg)?;
+            Some(Value::Array(
+                value.as_str()?.split(&separator).map(Value::from).collect(),
+            ))
+        }
+        "to_number" => match value {
+            Value::Number(_) => Some(value),
+            Value::String(s) => s.trim().parse().ok().map(number),
+            Value::Bool(b) => Some(Value::from(b as i64)),
+            _ => None,
+        },
+        "to_string" => Some(Value::String(json_value_to_string(&value))),
+        "regex" => {
+            // The first capture group, or the whole match without groups
+            let re = regex::Regex::new(&text(arg)?).ok()?;
+            let input = json_value_to_string(&value);
+            let captures = re.captures(&input)?;
+            let found = captures.get(1).or_else(|| captures.get(0))?;
+            Some(Value::from(found.as_str()))
+        }
+        _ => None,
+    }
+}
+
+/// The element itself, or the field an argument such as `.id` selects from it
+fn select(item: &Value, arg: Option<&PipeArg>) -> Option<Value> {
+    match arg {
+        None => Some(item.clone()),
+        Some(PipeArg::Literal(literal)) => Some(literal_to_json(literal)),
+        Some(PipeArg::Field(segments)) => segments
+            .iter()
+            .try_fold(item, |value, segment| match segment {
+                PathSegment::Property(name) => value.get(name),
+                PathSegment::Index(idx) => value.get(idx),
+                _ => None,
+            })
+            .cloned(),
+    }
+}
+
+fn text(arg: Option<&PipeArg>) -> Option<String> {
+    match arg? {
+        PipeArg::Literal(literal) => Some(json_value_to_string(&literal_to_json(literal))),
+        PipeArg::Field(_) => None,
+    }
+}
+
+fn into_array(value: Value) -> Option<Vec<Value>> {
+    match value {
+        Value::Array(items) => Some(items),
+        _ => None,
+    }
+}
+
+fn is_truthy(value: Option<&Value>) -> bool {
+    !matches!(value, None | Some(Value::Null) | Some(Value::Bool(false)))
+}
+
+/// The smallest or largest element, or field of an element
+fn extreme(value: Value, arg: Option<&PipeArg>, wanted: Ordering) -> Option<Value> {
+    value
+        .as_array()?
+        .iter()
+        .filter_map(|item| select(item, arg))
+        .reduce(|best, item| {
+            if order(Some(&item), Some(&best)) == wanted {
+                item
+            } else {
+                best
+            }
+        })
+}
+
+/// Order numbers numerically and strings lexically; other values sort by type
+fn order(a: Option<&Value>, b: Option<&Value>) -> Ordering {
+    fn rank(value: Option<&Value>) -> u8 {
+        match value {
+            None | Some(Value::Null) => 0,
+            Some(Value::Bool(_)) => 1,
+            Some(Value::Number(_)) => 2,
+            Some(Value::String(_)) => 3,
+            Some(Value::Array(_)) => 4,
+            Some(Value::Object(_)) => 5,
+        }
+    }
+
+    match (a, b) {
+        (Some(Value::Number(a)), Some(Value::Number(b))) => a
+            .as_f64()
+            .partial_cmp(&b.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Some(Value::String(a)), Some(Value::String(b))) => a.cmp(b),
+        (Some(Value::Bool(a)), Some(Value::Bool(b))) => a.cmp(b),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+/// Whole results stay integers, so `sum` of `[1, 2]` is `3` rather than `3.0`
+fn number(n: f64) -> Value {
+    if n.fract() == 0.0 && n.abs() < i64::MAX as f64 {
+        Value::from(n as i64)
+    } else {
+        Value::from(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::Response;
+    use crate::parser::parse_expression;
+    use crate::runtime::Evaluator;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    fn evaluate(expression: &str) -> Option<Value> {
+        let response = Response::test_json(
+            200,
+            json!({
+                "items": [
+                    { "id": 3, "name": " Carol ", "active": true, "price": 2.5 },
+                    { "id": 1, "name": "alice", "active": false, "price": 10 },
+                    { "id": 2, "name": "Bob", "active": true, "price": 4 }
+                ],
+                "tags": "b,a,b",
+                "count": "42"
+            }),
+        );
+        let variables = HashMap::new();
+        let expression = parse_expression(expression).unwrap();
+        Evaluator::new(&response, &variables).evaluate(&expression)
+    }
+
+    #[test]
+    fn test_chained_pipes() {
+        assert_eq!(
+            evaluate(r#"res.body.items | filter(.active) | map(.id) | join(",")"#),
+            Some(json!("3,2"))
+        );
+        assert_eq!(
+            evaluate("body.items | sort(.id) | first | keys | length"),
+            Some(json!(4))
+        );
+        assert_eq!(
+            evaluate("body.items | map(.name) | last | upper"),
+            Some(json!("BOB"))
+        );
+        assert_eq!(
+            evaluate("body.items | sort(.name) | map(.name) | first | trim | lower"),
+            Some(json!("carol"))
+        );
+    }
+
+    #[test]
+    fn test_aggregates_and_conversions() {
+        assert_eq!(evaluate("body.items | sum(.price)"), Some(json!(16.5)));
+        assert_eq!(evaluate("body.items | map(.id) | sum"), Some(json!(6)));
+        assert_eq!(evaluate("body.items | min(.price)"), Some(json!(2.5)));
+        assert_eq!(evaluate("body.items | max(.id)"), Some(json!(3)));
+        assert_eq!(
+            evaluate(r#"body.tags | split(",") | unique | sort | reverse"#),
+            Some(json!(["b", "a"]))
+        );
+        assert_eq!(evaluate("body.count | to_number"), Some(json!(42)));
+        assert_eq!(
+            evaluate("body.items | length | to_string"),
+            Some(json!("3"))
+        );
+        assert_eq!(evaluate("body.tags | sum"), None);
+    }
+}
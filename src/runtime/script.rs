@@ -0,0 +1,373 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Sandboxed Rhai scripts for `[pre-request]` and `[post-response]`
+//!
+//! Scripts see four maps: `req` (method, url, headers, query, body), `res`
+//! (status, headers, body, duration, size; post-response only), `vars` and
+//! `env`, the environment variables `execution.env_allowlist` lets through.
+//! Changes to `vars` are kept for later requests and changes to `req`
+//! in a pre-request script are sent. `fail(message)` and
+//! `assert(condition, message)` record failures without stopping the script.
+
+use super::{functions, json_value_to_string};
+use crate::http::Response;
+use crate::parser::{BodySection, ReqxFile, Script};
+use anyhow::{anyhow, Result};
+use rhai::{Dynamic, Engine, Map, Scope};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Upper bound on operations a script may run, so loops cannot hang a run
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// Upper bounds on the values a script may build, so it cannot exhaust
+/// memory before reaching `MAX_OPERATIONS`
+const MAX_STRING_SIZE: usize = 16 * 1024 * 1024;
+const MAX_ARRAY_SIZE: usize = 1_000_000;
+const MAX_MAP_SIZE: usize = 100_000;
+
+/// What a script changed and reported
+#[derive(Debug, Default)]
+pub struct ScriptOutcome {
+    /// Variables the script set or changed. Untouched ones are left out, so
+    /// values rhai cannot represent exactly are not rewritten.
+    pub variables: HashMap<String, Value>,
+    /// Variables the script removed from `vars`
+    pub removed: Vec<String>,
+    pub failures: Vec<String>,
+    /// The `req` map as the script left it
+    pub request: Map,
+}
+
+/// Run a script against a request and, after it is sent, its response
+pub fn run(
+    script: &Script,
+    reqx_file: &ReqxFile,
+    response: Option<&Response>,
+    variables: &HashMap<String, Value>,
+    env: &HashMap<String, String>,
+) -> Result<ScriptOutcome> {
+    let failures = Arc::new(Mutex::new(Vec::new()));
+    let engine = engine(failures.clone());
+
+    let mut scope = Scope::new();
+    scope.push("req", request_map(reqx_file)?);
+    if let Some(response) = response {
+        scope.push("res", response_map(response)?);
+    }
+    scope.push("vars", rhai::serde::to_dynamic(variables)?);
+    scope.push_constant(
+        "env",
+        env.iter()
+            .map(|(k, v)| (k.into(), Dynamic::from(v.clone())))
+            .collect::<Map>(),
+    );
+
+    engine
+        .run_with_scope(&mut scope, &script.source)
+        .map_err(|e| anyhow!("{} ({})", e, script.origin.display()))?;
+
+    let after: HashMap<String, Value> = match scope.get_value::<Dynamic>("vars") {
+        Some(vars) => rhai::serde::from_dynamic(&vars)?,
+        None => HashMap::new(),
+    };
+    // Compare against the input as it looks after the same round trip, so
+    // only the script's own changes count
+    let before: HashMap<String, Value> =
+        rhai::serde::from_dynamic(&rhai::serde::to_dynamic(variables)?)?;
+    let removed = before
+        .keys()
+        .filter(|name| !after.contains_key(*name))
+        .cloned()
+        .collect();
+    let variables = after
+        .into_iter()
+        .filter(|(name, value)| before.get(name) != Some(value))
+        .collect();
+    let request = scope.get_value::<Map>("req").unwrap_or_default();
+    let failures = failures.lock().map(|f| f.clone()).unwrap_or_default();
+
+    Ok(ScriptOutcome {
+        variables,
+        removed,
+        failures,
+        request,
+    })
+}
+
+/// Copy the `req` map a pre-request script modified back into the request
+pub fn apply_request(reqx_file: &mut ReqxFile, request: &Map) -> Result<()> {
+    if let Some(method) = request.get("method") {
+        reqx_file.request.method = method.to_string().to_uppercase();
+    }
+    if let Some(url) = request.get("url") {
+        reqx_file.request.url = url.to_string();
+    }
+    if let Some(headers) = request.get("headers").and_then(|h| h.read_lock::<Map>()) {
+        reqx_file.headers = string_map(&headers);
+    }
+    if let Some(query) = request.get("query").and_then(|q| q.read_lock::<Map>()) {
+        reqx_file.query = string_map(&query);
+    }
+    if let Some(body) = request.get("body") {
+        reqx_file.body = if body.is_unit() {
+            None
+        } else if body.is_string() {
+            Some(BodySection::Raw(body.to_string()))
+        } else if let Some(BodySection::FormData(_)) = reqx_file.body {
+            let form = body.read_lock::<Map>().map(|m| string_map(&m));
+            Some(BodySection::FormData(form.unwrap_or_default()))
+        } else {
+            Some(BodySection::Json(rhai::serde::from_dynamic::<Value>(body)?))
+        };
+    }
+    Ok(())
+}
+
+fn engine(failures: Arc<Mutex<Vec<String>>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_call_levels(32);
+    engine.set_max_string_size(MAX_STRING_SIZE);
+    engine.set_max_array_size(MAX_ARRAY_SIZE);
+    engine.set_max_map_size(MAX_MAP_SIZE);
+    engine.disable_symbol("eval");
+    // Keep stdout for reports
+    engine.on_print(|s| eprintln!("{}", s));
+    engine.on_debug(|s, _, pos| eprintln!("{:?} {}", pos, s));
+
+    let on_fail = failures.clone();
+    engine.register_fn("fail", move |message: &str| {
+        if let Ok(mut failures) = on_fail.lock() {
+            failures.push(message.to_string());
+        }
+    });
+    engine.register_fn("assert", move |condition: bool, message: &str| {
+        if !condition {
+            if let Ok(mut failures) = failures.lock() {
+                failures.push(message.to_string());
+            }
+        }
+    });
+
+    engine.register_fn("sha256", functions::sha256);
+    engine.register_fn("hmac_sha256", functions::hmac_sha256);
+    engine.register_fn("base64_encode", functions::base64_encode);
+    engine.register_fn("base64_decode", |input: &str| {
+        functions::base64_decode(input).unwrap_or_default()
+    });
+    engine.register_fn("urlencode", functions::urlencode);
+    engine.register_fn("to_json", |value: Dynamic| {
+        rhai::serde::from_dynamic::<Value>(&value)
+            .map(|v| v.to_string())
+            .unwrap_or_default()
+    });
+    engine.register_fn("parse_json", |input: &str| -> Dynamic {
+        serde_json::from_str::<Value>(input)
+            .ok()
+            .and_then(|v| rhai::serde::to_dynamic(v).ok())
+            .unwrap_or(Dynamic::UNIT)
+    });
+
+    engine
+}
+
+fn request_map(reqx_file: &ReqxFile) -> Result<Map> {
+    let body = match &reqx_file.body {
+        Some(BodySection::Json(json)) => rhai::serde::to_dynamic(json)?,
+        Some(BodySection::Raw(raw)) => Dynamic::from(raw.clone()),
+        Some(BodySection::FormData(form)) => rhai::serde::to_dynamic(form)?,
+        None => Dynamic::UNIT,
+    };
+
+    let mut map = Map::new();
+    map.insert("method".into(), reqx_file.request.method.clone().into());
+    map.insert("url".into(), reqx_file.request.url.clone().into());
+    map.insert(
+        "headers".into(),
+        rhai::serde::to_dynamic(&reqx_file.headers)?,
+    );
+    map.insert("query".into(), rhai::serde::to_dynamic(&reqx_file.query)?);
+    map.insert("body".into(), body);
+    Ok(map)
+}
+
+fn response_map(response: &Response) -> Result<Map> {
+    let headers: Map = response
+        .headers
+        .names()
+        .into_iter()
+        .map(|name| {
+            let value = response.headers.get_all(name).join(", ");
+            (name.to_ascii_lowercase().into(), Dynamic::from(value))
+        })
+        .collect();
+
+    let mut map = Map::new();
+    map.insert("status".into(), Dynamic::from(response.status as i64));
+    map.insert("headers".into(), headers.into());
+    map.insert("body".into(), rhai::serde::to_dynamic(&response.body)?);
+    map.insert(
+        "duration".into(),
+        Dynamic::from(response.duration.as_millis() as i64),
+    );
+    map.insert("size".into(), Dynamic::from(response.size as i64));
+    Ok(map)
+}
+
+fn string_map(map: &Map) -> HashMap<String, String> {
+    map.iter()
+        .map(|(k, v)| (k.to_string(), dynamic_to_string(v.clone())))
+        .collect()
+}
+
+/// Header and query values are strings; anything else becomes its JSON text
+fn dynamic_to_string(value: Dynamic) -> String {
+    if value.is_string() {
+        return value.to_string();
+    }
+    rhai::serde::from_dynamic::<Value>(&value)
+        .map(|v| json_value_to_string(&v))
+        .unwrap_or_else(|_| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_content;
+    use std::path::{Path, PathBuf};
+
+    fn script(source: &str) -> Script {
+        Script {
+            origin: PathBuf::from("test.reqx"),
+            source: source.to_string(),
+        }
+    }
+
+    fn reqx_file() -> ReqxFile {
+        let content = r#"
+[request]
+method = "POST"
+url = "https://api.example.com/orders"
+
+[body]
+item = "book"
+"#;
+        parse_content(content, Path::new("test.reqx")).unwrap()
+    }
+
+    #[test]
+    fn test_pre_request_script_modifies_request() {
+        let mut reqx_file = reqx_file();
+        let source = r#"
+            let payload = to_json(req.body);
+            req.headers["X-Signature"] = hmac_sha256(vars.secret, payload);
+            req.body.quantity = 2;
+            vars.signed = true;
+        "#;
+        let variables = HashMap::from([("secret".to_string(), Value::from("s3cr3t"))]);
+
+        let outcome = run(
+            &script(source),
+            &reqx_file,
+            None,
+            &variables,
+            &HashMap::new(),
+        )
+        .unwrap();
+        apply_request(&mut reqx_file, &outcome.request).unwrap();
+
+        assert_eq!(reqx_file.headers["X-Signature"].len(), 64);
+        assert!(matches!(
+            reqx_file.body,
+            Some(BodySection::Json(ref body)) if body["quantity"] == 2
+        ));
+        assert_eq!(outcome.variables["signed"], true);
+    }
+
+    #[test]
+    fn test_post_response_script_reports_failures() {
+        let response = Response {
+            headers: [("Location", "/orders/42")].into_iter().collect(),
+            ..Response::test_json(201, serde_json::json!({ "id": 42, "items": [1, 2] }))
+        };
+        let source = r#"
+            if res.status == 201 { vars.order_id = res.body.id; }
+            assert(res.body.items.len() == 3, "expected 3 items");
+            if res.headers.location != "/orders/42" { fail("wrong location"); }
+        "#;
+
+        let outcome = run(
+            &script(source),
+            &reqx_file(),
+            Some(&response),
+            &HashMap::new(),
+            &HashMap::new(),
+        )
+        .unwrap();
+        assert_eq!(outcome.variables["order_id"], 42);
+        assert_eq!(outcome.failures, vec!["expected 3 items".to_string()]);
+    }
+
+    #[test]
+    fn test_script_reports_only_changed_variables() {
+        let source = r#"
+            vars.count = vars.count + 1;
+            vars.remove("stale");
+        "#;
+        let variables = HashMap::from([
+            ("count".to_string(), Value::from(1)),
+            ("stale".to_string(), Value::from("x")),
+            (
+                "user".to_string(),
+                serde_json::json!({ "id": u64::MAX, "roles": ["admin", null] }),
+            ),
+        ]);
+
+        let outcome = run(
+            &script(source),
+            &reqx_file(),
+            None,
+            &variables,
+            &HashMap::new(),
+        )
+        .unwrap();
+        assert_eq!(
+            outcome.variables,
+            HashMap::from([("count".to_string(), Value::from(2))])
+        );
+        assert_eq!(outcome.removed, vec!["stale".to_string()]);
+    }
+
+    #[test]
+    fn test_script_is_sandboxed() {
+        let source = "loop { }";
+        let err = run(
+            &script(source),
+            &reqx_file(),
+            None,
+            &HashMap::new(),
+            &HashMap::new(),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("operations"), "{}", err);
+
+        for source in [
+            r#"let s = "x"; loop { s += s; }"#,
+            "let a = [0]; loop { a += a; }",
+        ] {
+            let err = run(
+                &script(source),
+                &reqx_file(),
+                None,
+                &HashMap::new(),
+                &HashMap::new(),
+            )
+            .unwrap_err();
+            assert!(err.to_string().contains("too large"), "{}", err);
+        }
+    }
+}
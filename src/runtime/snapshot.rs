@@ -0,0 +1,208 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Snapshot storage and comparison
+
+use super::diff::{diff, DiffEntry};
+use crate::parser::ast::PathSegment;
+use anyhow::{Context, Result};
+use jsonpath_rust::{JsonPathFinder, JsonPathInst, JsonPathValue};
+use serde_json::{Map, Value};
+use std::path::Path;
+
+/// Stored in place of ignored values, so their presence is still checked
+const IGNORED: &str = "[ignored]";
+
+/// Result of checking a value against its snapshot
+#[derive(Debug)]
+pub enum SnapshotOutcome {
+    /// No snapshot existed, so the value was stored
+    Recorded,
+    /// The stored snapshot was overwritten by `--update-snapshots`
+    Updated,
+    Matched,
+    Mismatched(Vec<DiffEntry>),
+}
+
+/// Compare a value with the snapshot stored under `key`, recording it when
+/// there is none yet or when `update` is set. A snapshot file holds one
+/// entry per snapshotted expression.
+pub fn compare(file: &Path, key: &str, actual: &Value, update: bool) -> Result<SnapshotOutcome> {
+    let mut snapshots: Map<String, Value> = if file.exists() {
+        let content = std::fs::read_to_string(file)
+            .with_context(|| format!("Failed to read snapshot: {}", file.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Invalid snapshot file: {}", file.display()))?
+    } else {
+        Map::new()
+    };
+
+    let outcome = match snapshots.get(key) {
+        Some(_) if update => SnapshotOutcome::Updated,
+        Some(stored) => {
+            let entries = diff(stored, actual);
+            return Ok(if entries.is_empty() {
+                SnapshotOutcome::Matched
+            } else {
+                SnapshotOutcome::Mismatched(entries)
+            });
+        }
+        None => SnapshotOutcome::Recorded,
+    };
+
+    snapshots.insert(key.to_string(), actual.clone());
+    let content = serde_json::to_string_pretty(&snapshots)?;
+    std::fs::write(file, content + "\n")
+        .with_context(|| format!("Failed to write snapshot: {}", file.display()))?;
+
+    Ok(outcome)
+}
+
+/// Replace every value a body path matches with a placeholder
+pub fn redact(value: &mut Value, segments: &[PathSegment]) {
+    let Some((segment, rest)) = segments.split_first() else {
+        *value = Value::String(IGNORED.to_string());
+        return;
+    };
+
+    match segment {
+        PathSegment::Property(name) => {
+            if let Some(v) = value.get_mut(name) {
+                redact(v, rest);
+            }
+        }
+        PathSegment::Index(idx) => {
+            if let Some(v) = value.get_mut(*idx) {
+                redact(v, rest);
+            }
+        }
+        PathSegment::Wildcard => {
+            if let Value::Array(items) = value {
+                for item in items {
+                    redact(item, rest);
+                }
+            }
+        }
+        PathSegment::Query { path, .. } => {
+            for pointer in query_pointers(value, path) {
+                if let Some(v) = value.pointer_mut(&pointer) {
+                    redact(v, rest);
+                }
+            }
+        }
+        // Ignore paths are not interpolated
+        PathSegment::Variable(_) => {}
+    }
+}
+
+/// JSON pointers to every match of a JSONPath query
+fn query_pointers(value: &Value, path: &str) -> Vec<String> {
+    let Ok(query) = path.parse::<JsonPathInst>() else {
+        return Vec::new();
+    };
+    let finder = JsonPathFinder::new(Box::new(value.clone()), Box::new(query));
+
+    finder
+        .find_slice()
+        .into_iter()
+        .filter_map(|m| match m {
+            JsonPathValue::Slice(_, location) => Some(location_to_pointer(&location)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Convert a match location such as `$.['data'][3].['id']` to `/data/3/id`
+fn location_to_pointer(location: &str) -> String {
+    let mut pointer = String::new();
+    let mut rest = location.trim_start_matches('$');
+
+    while let Some(start) = rest.find('[') {
+        let after = &rest[start + 1..];
+        let (token, remaining) = match after.strip_prefix('\'') {
+            Some(quoted) => match quoted.split_once("']") {
+                Some((key, remaining)) => (key, remaining),
+                None => break,
+            },
+            None => match after.split_once(']') {
+                Some((index, remaining)) => (index, remaining),
+                None => break,
+            },
+        };
+        pointer.push('/');
+        pointer.push_str(&token.replace('~', "~0").replace('/', "~1"));
+        rest = remaining;
+    }
+
+    pointer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ast::Expression;
+    use crate::parser::parse_expression;
+    use serde_json::json;
+
+    fn redact_path(value: &mut Value, path: &str) {
+        match parse_expression(path).unwrap() {
+            Expression::Path(path) => redact(value, &path.segments),
+            other => panic!("not a path: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_redact_ignored_paths() {
+        let mut body = json!({
+            "id": 7,
+            "data": [{ "id": 1, "at": "2024-01-01" }, { "id": 2, "meta": { "at": "2024-01-02" } }]
+        });
+
+        redact_path(&mut body, "body.id");
+        redact_path(&mut body, "body.data[*].id");
+        redact_path(&mut body, "body..at");
+
+        assert_eq!(
+            body,
+            json!({
+                "id": IGNORED,
+                "data": [
+                    { "id": IGNORED, "at": IGNORED },
+                    { "id": IGNORED, "meta": { "at": IGNORED } }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_compare_records_then_matches() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("users.snap.json");
+
+        let first = json!({ "name": "Alice" });
+        assert!(matches!(
+            compare(&file, "body", &first, false).unwrap(),
+            SnapshotOutcome::Recorded
+        ));
+        assert!(matches!(
+            compare(&file, "body", &first, false).unwrap(),
+            SnapshotOutcome::Matched
+        ));
+
+        let changed = json!({ "name": "Bob" });
+        match compare(&file, "body", &changed, false).unwrap() {
+            SnapshotOutcome::Mismatched(entries) => assert_eq!(entries[0].pointer, "/name"),
+            other => panic!("expected mismatch, got {:?}", other),
+        }
+
+        assert!(matches!(
+            compare(&file, "body", &changed, true).unwrap(),
+            SnapshotOutcome::Updated
+        ));
+        assert!(matches!(
+            compare(&file, "body", &changed, false).unwrap(),
+            SnapshotOutcome::Matched
+        ));
+    }
+}
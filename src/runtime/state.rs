@@ -0,0 +1,123 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Variables persisted between runs in `.reqx/state/<env>.toml`
+//!
+//! Values are stored as JSON text so objects, numbers and nulls come back
+//! with the type they were captured with.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const STATE_DIR: &str = ".reqx/state";
+
+/// State file name when no environment is selected
+const DEFAULT_STATE: &str = "default";
+
+/// Contents of a state file
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct State {
+    #[serde(default)]
+    pub variables: BTreeMap<String, StoredVariable>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredVariable {
+    /// The value as JSON text
+    pub value: String,
+    pub saved_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl StoredVariable {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    pub fn value(&self) -> Value {
+        serde_json::from_str(&self.value).unwrap_or_else(|_| Value::String(self.value.clone()))
+    }
+}
+
+/// Where the state of an environment is stored
+pub fn state_path(environment: Option<&str>) -> PathBuf {
+    Path::new(STATE_DIR).join(format!("{}.toml", environment.unwrap_or(DEFAULT_STATE)))
+}
+
+impl State {
+    /// Read a state file, which is empty when it does not exist yet
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read state file: {}", path.display()))?;
+        toml::from_str(&content).with_context(|| format!("Invalid state file: {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let content = toml::to_string_pretty(self)?;
+        std::fs::write(path, content)
+            .with_context(|| format!("Failed to write state file: {}", path.display()))
+    }
+
+    pub fn set(&mut self, name: &str, value: &Value, ttl: Option<Duration>, now: DateTime<Utc>) {
+        let expires_at = ttl
+            .and_then(|ttl| chrono::Duration::from_std(ttl).ok())
+            .map(|ttl| now + ttl);
+        self.variables.insert(
+            name.to_string(),
+            StoredVariable {
+                value: value.to_string(),
+                saved_at: now,
+                expires_at,
+            },
+        );
+    }
+
+    /// Variables that have not expired
+    pub fn live(&self, now: DateTime<Utc>) -> impl Iterator<Item = (&String, Value)> {
+        self.variables
+            .iter()
+            .filter(move |(_, stored)| !stored.is_expired(now))
+            .map(|(name, stored)| (name, stored.value()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_state_round_trip_and_expiry() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state/local.toml");
+        let now = Utc::now();
+
+        let mut state = State::load(&path).unwrap();
+        state.set("token", &json!("abc"), Some(Duration::from_secs(60)), now);
+        state.set("user", &json!({ "id": 7, "manager": null }), None, now);
+        state.save(&path).unwrap();
+
+        let state = State::load(&path).unwrap();
+        let live: BTreeMap<_, _> = state.live(now).collect();
+        assert_eq!(live[&"token".to_string()], json!("abc"));
+        assert_eq!(live[&"user".to_string()]["id"], 7);
+
+        let later = now + chrono::Duration::minutes(2);
+        let live: Vec<_> = state.live(later).map(|(name, _)| name.clone()).collect();
+        assert_eq!(live, vec!["user".to_string()]);
+    }
+}